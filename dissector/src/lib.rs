@@ -0,0 +1,184 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+//! Offline recsync protocol dissector.
+//!
+//! Reads a pcap or pcapng capture, decodes the UDP announcements and reassembles the TCP
+//! connections to RecCeivers, then prints every session as a `wire::Transcript`.
+//! RecCeiver ports are learnt from the announcements, given with `--port`, or detected from
+//! connections that start with the "RC" magic number.
+
+pub mod packet;
+pub mod pcap;
+pub mod session;
+pub mod stream;
+
+use std::{collections::{HashMap, HashSet}, io, net::SocketAddr, time::Duration};
+
+use packet::{Transport, TcpSegment, UdpDatagram};
+use pcap::Packet;
+use session::Session;
+use wire::{Announcement, MSG_MAGIC_ID, SERVER_ANNOUNCEMENT_UDP_PORT};
+
+/// Connection key that is the same for both directions
+type FlowKey = (SocketAddr, SocketAddr);
+
+fn flow_key(a: SocketAddr, b: SocketAddr) -> FlowKey {
+    if a <= b { (a, b) } else { (b, a) }
+}
+
+/// Decoded announcements and sessions of a capture
+#[derive(Default)]
+pub struct Dissector {
+    ports: HashSet<u16>,
+    announcements: Vec<String>,
+    sessions: HashMap<FlowKey, Session>,
+    /// Sessions whose 4-tuple was reused by a later connection
+    finished: Vec<Session>,
+    /// SYNs seen before any payload, the first one comes from the client
+    syns: HashMap<FlowKey, Vec<(SocketAddr, u32)>>,
+    /// Connections that turned out not to carry recsync traffic
+    ignored: HashSet<FlowKey>,
+}
+
+impl Dissector {
+    pub fn new() -> Dissector {
+        Dissector::default()
+    }
+
+    /// Treat connections to or from `port` as recsync traffic
+    pub fn add_port(&mut self, port: u16) {
+        self.ports.insert(port);
+    }
+
+    /// Decode one captured packet, packets have to be fed in capture order
+    pub fn packet(&mut self, packet: &Packet) {
+        match packet::parse(packet.linktype, packet.data) {
+            Some(Transport::Udp(datagram)) => self.udp(packet.timestamp, datagram),
+            Some(Transport::Tcp(segment)) => self.tcp(packet.timestamp, segment),
+            None => {},
+        }
+    }
+
+    fn udp(&mut self, timestamp: Duration, datagram: UdpDatagram) {
+        if datagram.dst.port() != SERVER_ANNOUNCEMENT_UDP_PORT {
+            return;
+        }
+        let line = match Announcement::decode(datagram.payload) {
+            Ok(msg) => {
+                self.ports.insert(msg.server_port);
+                format!("Announcement v{} server={} key={:#010x}", msg.version(), msg.resolve(datagram.src), msg.server_key)
+            },
+            Err(err) => format!("decode error: {}", err),
+        };
+        self.announcements.push(format!("{} {} -> {} {}", format_timestamp(timestamp), datagram.src, datagram.dst, line));
+    }
+
+    fn tcp(&mut self, timestamp: Duration, segment: TcpSegment) {
+        let key = flow_key(segment.src, segment.dst);
+        if segment.syn && !segment.ack {
+            // A new connection reusing the 4-tuple of an earlier one
+            if let Some(session) = self.sessions.remove(&key) {
+                self.finished.push(session);
+            }
+            self.ignored.remove(&key);
+            self.syns.remove(&key);
+        }
+        if self.ignored.contains(&key) {
+            return;
+        }
+        if segment.syn && segment.payload.is_empty() {
+            match self.sessions.get_mut(&key) {
+                Some(session) => session.syn(segment.src, segment.seq),
+                None => self.syns.entry(key).or_default().push((segment.src, segment.seq)),
+            }
+            return;
+        }
+        if !self.sessions.contains_key(&key) {
+            if segment.payload.is_empty() {
+                return;
+            }
+            let server = match self.server_of(key, &segment) {
+                Some(server) => server,
+                None => {
+                    self.ignored.insert(key);
+                    return;
+                },
+            };
+            let client = if server == segment.src { segment.dst } else { segment.src };
+            let mut session = Session::new(client, server, timestamp);
+            for (src, seq) in self.syns.remove(&key).unwrap_or_default() {
+                session.syn(src, seq);
+            }
+            self.sessions.insert(key, session);
+        }
+        if let Some(session) = self.sessions.get_mut(&key) {
+            session.segment(timestamp, segment.src, segment.seq, segment.payload);
+        }
+    }
+
+    /// Work out which end of a new connection is the RecCeiver, `None` if it is not recsync traffic
+    fn server_of(&self, key: FlowKey, segment: &TcpSegment) -> Option<SocketAddr> {
+        if self.ports.contains(&segment.dst.port()) {
+            return Some(segment.dst);
+        }
+        if self.ports.contains(&segment.src.port()) {
+            return Some(segment.src);
+        }
+        let payload = segment.payload;
+        if payload.len() < 4 || u16::from_be_bytes([payload[0], payload[1]]) != MSG_MAGIC_ID {
+            return None;
+        }
+        if let Some((client, _)) = self.syns.get(&key).and_then(|syns| syns.first()) {
+            return Some(if *client == segment.src { segment.dst } else { segment.src });
+        }
+        // Server to client message IDs have the high bit set
+        let msg_id = u16::from_be_bytes([payload[2], payload[3]]);
+        Some(if msg_id & 0x8000 != 0 { segment.src } else { segment.dst })
+    }
+
+    /// Write the announcements, then every session in the order they started.
+    /// Messages use the `Transcript` syntax, everything else is a `#` comment so the output can be
+    /// compared with or replayed as a transcript.
+    pub fn write(&self, out: &mut impl io::Write) -> io::Result<()> {
+        if !self.announcements.is_empty() {
+            writeln!(out, "# Announcements")?;
+            for line in &self.announcements {
+                writeln!(out, "# {}", line)?;
+            }
+            writeln!(out)?;
+        }
+        let mut sessions: Vec<&Session> = self.sessions.values().chain(&self.finished).filter(|session| !session.lines.is_empty()).collect();
+        sessions.sort_by_key(|session| session.start);
+        for session in sessions {
+            writeln!(out, "# Session {} -> {} started {}", session.client, session.server, format_timestamp(session.start))?;
+            for line in &session.lines {
+                writeln!(out, "{}", line)?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+}
+
+/// Format a capture timestamp as UTC date and time with microseconds
+pub fn format_timestamp(timestamp: Duration) -> String {
+    let secs = timestamp.as_secs();
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:06}", year, month, day, secs_of_day / 3600, secs_of_day / 60 % 60, secs_of_day % 60, timestamp.subsec_micros())
+}
+
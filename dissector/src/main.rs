@@ -0,0 +1,68 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+//! Command line front end of the dissector, prints the transcript of a capture file
+
+use std::{io, process::ExitCode};
+
+use dissector::{pcap, Dissector};
+
+const USAGE: &str = "usage: recsync-dissector [--port PORT]... <capture.pcap|capture.pcapng>";
+
+fn main() -> ExitCode {
+    let mut dissector = Dissector::new();
+    let mut path = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            },
+            "-p" | "--port" => match args.next().and_then(|port| port.parse().ok()) {
+                Some(port) => dissector.add_port(port),
+                None => {
+                    eprintln!("--port expects a TCP port number\n{}", USAGE);
+                    return ExitCode::FAILURE;
+                },
+            },
+            _ if path.is_none() => path = Some(arg),
+            _ => {
+                eprintln!("{}", USAGE);
+                return ExitCode::FAILURE;
+            },
+        }
+    }
+    let Some(path) = path else {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    };
+
+    let data = match std::fs::read(&path) {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("cannot read {}: {}", path, err);
+            return ExitCode::FAILURE;
+        },
+    };
+    let packets = match pcap::packets(&data) {
+        Ok(packets) => packets,
+        Err(err) => {
+            eprintln!("cannot parse {}: {}", path, err);
+            return ExitCode::FAILURE;
+        },
+    };
+
+    for packet in &packets {
+        dissector.packet(packet);
+    }
+    if let Err(err) = dissector.write(&mut io::stdout().lock()) {
+        eprintln!("cannot write the transcript: {}", err);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,133 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+//! Just enough link, network and transport layer parsing to find TCP segments and UDP datagrams
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+const LINKTYPE_NULL: u16 = 0;
+const LINKTYPE_ETHERNET: u16 = 1;
+const LINKTYPE_RAW: u16 = 101;
+const LINKTYPE_LINUX_SLL: u16 = 113;
+const LINKTYPE_IPV4: u16 = 228;
+const LINKTYPE_IPV6: u16 = 229;
+const LINKTYPE_LINUX_SLL2: u16 = 276;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88a8;
+
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+
+pub struct TcpSegment<'a> {
+    pub src: SocketAddr,
+    pub dst: SocketAddr,
+    pub seq: u32,
+    pub syn: bool,
+    pub ack: bool,
+    pub payload: &'a [u8],
+}
+
+pub struct UdpDatagram<'a> {
+    pub src: SocketAddr,
+    pub dst: SocketAddr,
+    pub payload: &'a [u8],
+}
+
+pub enum Transport<'a> {
+    Tcp(TcpSegment<'a>),
+    Udp(UdpDatagram<'a>),
+}
+
+fn be16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*data.get(at)?, *data.get(at + 1)?]))
+}
+
+fn be32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes([*data.get(at)?, *data.get(at + 1)?, *data.get(at + 2)?, *data.get(at + 3)?]))
+}
+
+/// Parse a captured frame down to its TCP or UDP payload, anything else yields `None`
+pub fn parse(linktype: u16, frame: &[u8]) -> Option<Transport<'_>> {
+    let (ethertype, ip) = match linktype {
+        LINKTYPE_ETHERNET => {
+            let mut ethertype = be16(frame, 12)?;
+            let mut offset = 14;
+            while ethertype == ETHERTYPE_VLAN || ethertype == ETHERTYPE_QINQ {
+                ethertype = be16(frame, offset + 2)?;
+                offset += 4;
+            }
+            (ethertype, frame.get(offset..)?)
+        },
+        LINKTYPE_LINUX_SLL => (be16(frame, 14)?, frame.get(16..)?),
+        LINKTYPE_LINUX_SLL2 => (be16(frame, 0)?, frame.get(20..)?),
+        LINKTYPE_NULL => {
+            // Address family in host byte order, 2 is AF_INET on every platform
+            let family = u32::from_le_bytes(frame.get(..4)?.try_into().ok()?);
+            let family = if family > 0xffff { family.swap_bytes() } else { family };
+            (if family == 2 { ETHERTYPE_IPV4 } else { ETHERTYPE_IPV6 }, frame.get(4..)?)
+        },
+        LINKTYPE_RAW => match frame.first()? >> 4 {
+            4 => (ETHERTYPE_IPV4, frame),
+            6 => (ETHERTYPE_IPV6, frame),
+            _ => return None,
+        },
+        LINKTYPE_IPV4 => (ETHERTYPE_IPV4, frame),
+        LINKTYPE_IPV6 => (ETHERTYPE_IPV6, frame),
+        _ => return None,
+    };
+
+    let (src, dst, protocol, segment) = match ethertype {
+        ETHERTYPE_IPV4 => {
+            let ihl = (*ip.first()? & 0x0f) as usize * 4;
+            let total_len = be16(ip, 2)? as usize;
+            // Fragments are not reassembled
+            if be16(ip, 6)? & 0x3fff != 0 {
+                return None;
+            }
+            let src = Ipv4Addr::from(be32(ip, 12)?);
+            let dst = Ipv4Addr::from(be32(ip, 16)?);
+            let end = total_len.min(ip.len());
+            (IpAddr::V4(src), IpAddr::V4(dst), ip[9], ip.get(ihl..end)?)
+        },
+        ETHERTYPE_IPV6 => {
+            let payload_len = be16(ip, 4)? as usize;
+            let src: [u8; 16] = ip.get(8..24)?.try_into().ok()?;
+            let dst: [u8; 16] = ip.get(24..40)?.try_into().ok()?;
+            let end = (40 + payload_len).min(ip.len());
+            // Extension headers are not followed
+            (IpAddr::V6(Ipv6Addr::from(src)), IpAddr::V6(Ipv6Addr::from(dst)), ip[6], ip.get(40..end)?)
+        },
+        _ => return None,
+    };
+
+    match protocol {
+        IPPROTO_TCP => {
+            let data_offset = (*segment.get(12)? >> 4) as usize * 4;
+            let flags = *segment.get(13)?;
+            Some(Transport::Tcp(TcpSegment {
+                src: SocketAddr::new(src, be16(segment, 0)?),
+                dst: SocketAddr::new(dst, be16(segment, 2)?),
+                seq: be32(segment, 4)?,
+                syn: flags & 0x02 != 0,
+                ack: flags & 0x10 != 0,
+                payload: segment.get(data_offset..)?,
+            }))
+        },
+        IPPROTO_UDP => {
+            let len = (be16(segment, 4)? as usize).min(segment.len());
+            Some(Transport::Udp(UdpDatagram {
+                src: SocketAddr::new(src, be16(segment, 0)?),
+                dst: SocketAddr::new(dst, be16(segment, 2)?),
+                payload: segment.get(8..len)?,
+            }))
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,165 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+//! Minimal offline reader for classic pcap and pcapng capture files
+
+use std::{io, time::Duration};
+
+/// A captured packet with its link layer type
+pub struct Packet<'a> {
+    pub timestamp: Duration,
+    pub linktype: u16,
+    pub data: &'a [u8],
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+#[derive(Copy, Clone)]
+enum Endian {
+    Little,
+    Big,
+}
+
+impl Endian {
+    fn u16(self, data: &[u8]) -> u16 {
+        let bytes = [data[0], data[1]];
+        match self {
+            Endian::Little => u16::from_le_bytes(bytes),
+            Endian::Big => u16::from_be_bytes(bytes),
+        }
+    }
+
+    fn u32(self, data: &[u8]) -> u32 {
+        let bytes = [data[0], data[1], data[2], data[3]];
+        match self {
+            Endian::Little => u32::from_le_bytes(bytes),
+            Endian::Big => u32::from_be_bytes(bytes),
+        }
+    }
+}
+
+/// Convert a timestamp counted in `per_second` ticks
+fn ticks_to_duration(ticks: u64, per_second: u64) -> Duration {
+    let nanos = (ticks % per_second) as u128 * 1_000_000_000 / per_second as u128;
+    Duration::new(ticks / per_second, nanos as u32)
+}
+
+/// Read all packets of a pcap or pcapng file held in memory
+pub fn packets(data: &[u8]) -> io::Result<Vec<Packet<'_>>> {
+    if data.len() < 4 {
+        return Err(invalid("file too short for a capture header"));
+    }
+    match &data[..4] {
+        [0xd4, 0xc3, 0xb2, 0xa1] => classic(data, Endian::Little, 1_000_000),
+        [0xa1, 0xb2, 0xc3, 0xd4] => classic(data, Endian::Big, 1_000_000),
+        [0x4d, 0x3c, 0xb2, 0xa1] => classic(data, Endian::Little, 1_000_000_000),
+        [0xa1, 0xb2, 0x3c, 0x4d] => classic(data, Endian::Big, 1_000_000_000),
+        [0x0a, 0x0d, 0x0d, 0x0a] => pcapng(data),
+        _ => Err(invalid("not a pcap or pcapng file")),
+    }
+}
+
+fn classic(data: &[u8], endian: Endian, per_second: u64) -> io::Result<Vec<Packet<'_>>> {
+    if data.len() < 24 {
+        return Err(invalid("truncated pcap header"));
+    }
+    let linktype = endian.u32(&data[20..24]) as u16;
+    let mut packets = Vec::new();
+    let mut offset = 24;
+    while offset + 16 <= data.len() {
+        let record = &data[offset..];
+        let seconds = endian.u32(&record[0..4]) as u64;
+        let fraction = endian.u32(&record[4..8]) as u64;
+        let caplen = endian.u32(&record[8..12]) as usize;
+        let end = offset + 16 + caplen;
+        if end > data.len() {
+            return Err(invalid("truncated pcap record"));
+        }
+        packets.push(Packet {
+            timestamp: Duration::from_secs(seconds) + ticks_to_duration(fraction, per_second),
+            linktype,
+            data: &data[offset + 16..end],
+        });
+        offset = end;
+    }
+    Ok(packets)
+}
+
+/// Interface description needed to interpret enhanced packet blocks
+struct Interface {
+    linktype: u16,
+    per_second: u64,
+}
+
+fn pcapng(data: &[u8]) -> io::Result<Vec<Packet<'_>>> {
+    let mut packets = Vec::new();
+    let mut interfaces: Vec<Interface> = Vec::new();
+    let mut endian = Endian::Little;
+    let mut offset = 0;
+    while offset + 12 <= data.len() {
+        let block = &data[offset..];
+        if block[..4] == [0x0a, 0x0d, 0x0d, 0x0a] {
+            // Section header, byte order magic decides the endianness of the section
+            endian = match block[8..12] {
+                [0x4d, 0x3c, 0x2b, 0x1a] => Endian::Little,
+                [0x1a, 0x2b, 0x3c, 0x4d] => Endian::Big,
+                _ => return Err(invalid("bad pcapng byte order magic")),
+            };
+            interfaces.clear();
+        }
+        let block_type = endian.u32(&block[0..4]);
+        let block_len = endian.u32(&block[4..8]) as usize;
+        if block_len < 12 || offset + block_len > data.len() {
+            return Err(invalid("truncated pcapng block"));
+        }
+        let body = &block[8..block_len - 4];
+        match block_type {
+            // Interface description block
+            0x0000_0001 if body.len() >= 8 => {
+                interfaces.push(Interface { linktype: endian.u16(&body[0..2]), per_second: if_tsresol(&body[8..], endian) });
+            },
+            // Enhanced packet block
+            0x0000_0006 if body.len() >= 20 => {
+                let interface = interfaces.get(endian.u32(&body[0..4]) as usize).ok_or_else(|| invalid("packet for unknown interface"))?;
+                let ticks = ((endian.u32(&body[4..8]) as u64) << 32) | endian.u32(&body[8..12]) as u64;
+                let caplen = endian.u32(&body[12..16]) as usize;
+                let packet = body.get(20..20 + caplen).ok_or_else(|| invalid("truncated enhanced packet block"))?;
+                packets.push(Packet { timestamp: ticks_to_duration(ticks, interface.per_second), linktype: interface.linktype, data: packet });
+            },
+            // Simple packet block, no timestamp
+            0x0000_0003 if body.len() >= 4 => {
+                let interface = interfaces.first().ok_or_else(|| invalid("packet for unknown interface"))?;
+                let caplen = (endian.u32(&body[0..4]) as usize).min(body.len() - 4);
+                packets.push(Packet { timestamp: Duration::ZERO, linktype: interface.linktype, data: &body[4..4 + caplen] });
+            },
+            _ => {},
+        }
+        offset += block_len;
+    }
+    Ok(packets)
+}
+
+/// Read the `if_tsresol` option of an interface description block, microseconds by default
+fn if_tsresol(mut options: &[u8], endian: Endian) -> u64 {
+    while options.len() >= 4 {
+        let code = endian.u16(&options[0..2]);
+        let len = endian.u16(&options[2..4]) as usize;
+        let padded = (len + 3) & !3;
+        if code == 0 || options.len() < 4 + padded {
+            break;
+        }
+        if code == 9 && len == 1 {
+            let resol = options[4];
+            let exponent = (resol & 0x7f) as u32;
+            return if resol & 0x80 == 0 { 10u64.saturating_pow(exponent) } else { 2u64.saturating_pow(exponent) };
+        }
+        options = &options[4 + padded..];
+    }
+    1_000_000
+}
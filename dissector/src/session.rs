@@ -0,0 +1,134 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+use std::{collections::HashMap, net::SocketAddr, time::Duration};
+
+use bytes::{Buf, Bytes, BytesMut};
+use wire::{session::SessionChecker, AddInfo, AddRecordType, DelRecord, Message, MessageCodec, MessageID, UnknownPolicy, Utf8Mode, MSG_MAGIC_ID};
+
+use crate::{format_timestamp, stream::HalfStream};
+
+/// Decoder state for one direction of a session
+struct Direction {
+    stream: HalfStream,
+    codec: MessageCodec,
+    failed: bool,
+    /// A gap was skipped, the stream continues somewhere inside a message
+    resync: bool,
+}
+
+impl Direction {
+    fn new() -> Direction {
+        // Show exactly what was sent, including names that are not UTF-8 and messages we do not know
+        let mut codec = MessageCodec::new();
+        codec.set_utf8_mode(Utf8Mode::Raw);
+        codec.set_unknown_policy(UnknownPolicy::Preserve);
+        Direction { stream: HalfStream::default(), codec, failed: false, resync: false }
+    }
+}
+
+/// A TCP connection between a RecCaster and a RecCeiver
+pub struct Session {
+    pub client: SocketAddr,
+    pub server: SocketAddr,
+    pub start: Duration,
+    to_server: Direction,
+    to_client: Direction,
+    records: HashMap<u32, Bytes>,
+    checker: SessionChecker,
+    pub lines: Vec<String>,
+}
+
+impl Session {
+    pub fn new(client: SocketAddr, server: SocketAddr, start: Duration) -> Session {
+        Session { client, server, start, to_server: Direction::new(), to_client: Direction::new(), records: HashMap::new(), checker: SessionChecker::new(), lines: Vec::new() }
+    }
+
+    pub fn syn(&mut self, src: SocketAddr, seq: u32) {
+        self.direction(src).stream.syn(seq);
+    }
+
+    /// Feed a TCP segment sent by `src` and decode every message it completes
+    pub fn segment(&mut self, timestamp: Duration, src: SocketAddr, seq: u32, payload: &[u8]) {
+        let sender = if src == self.client { wire::Direction::ClientToServer } else { wire::Direction::ServerToClient };
+        let direction = self.direction(src);
+        if direction.failed {
+            return;
+        }
+        let appended = direction.stream.segment(seq, payload);
+        if let Some(gap) = direction.stream.take_gap() {
+            direction.resync = true;
+            self.lines.push(format!("# {} {} gap of {} bytes", format_timestamp(timestamp), sender, gap));
+        }
+        if !appended {
+            return;
+        }
+        let direction = self.direction(src);
+        // After a gap, decoding continues once the next message header turned up
+        if direction.resync && !resync(&mut direction.stream.buf) {
+            return;
+        }
+        direction.resync = false;
+        let mut msgs = Vec::new();
+        loop {
+            match direction.codec.decode_message(&mut direction.stream.buf) {
+                Ok(Some(msg)) => msgs.push(Ok(msg)),
+                Ok(None) => break,
+                Err(err) => {
+                    // The stream cannot be resynchronised after a framing error
+                    direction.failed = true;
+                    msgs.push(Err(err));
+                    break;
+                },
+            }
+        }
+        for msg in msgs {
+            let line = match msg {
+                Ok(msg) => {
+                    let mut line = format!("{} {}  # {}", sender, msg, format_timestamp(timestamp));
+                    if let Some(name) = self.annotate(&msg) {
+                        line.push_str(&format!(" {:?}", String::from_utf8_lossy(&name)));
+                    }
+                    if let Err(err) = self.checker.check(sender, &msg) {
+                        line.push_str(&format!(" protocol violation: {}", err));
+                    }
+                    line
+                },
+                Err(err) => format!("# {} {} decode error: {}", format_timestamp(timestamp), sender, err),
+            };
+            self.lines.push(line);
+        }
+    }
+
+    fn direction(&mut self, src: SocketAddr) -> &mut Direction {
+        if src == self.client { &mut self.to_server } else { &mut self.to_client }
+    }
+
+    /// Remember record names and return the name of the record a message refers to
+    fn annotate(&mut self, msg: &Message) -> Option<Bytes> {
+        match msg {
+            Message::AddRecord(msg) if msg.atype != AddRecordType::Alias as u8 => {
+                self.records.insert(msg.recid, msg.rname.clone());
+                None
+            },
+            Message::AddInfo(AddInfo { recid, .. }) | Message::DelRecord(DelRecord { recid }) => self.records.get(recid).cloned(),
+            _ => None,
+        }
+    }
+}
+
+/// Skip to the next header with the magic number and a known message ID, returns false if there is none yet.
+/// The last bytes are kept as they may be the start of a header that is not complete.
+fn resync(buf: &mut BytesMut) -> bool {
+    let magic = MSG_MAGIC_ID.to_be_bytes();
+    let found = buf.windows(4).position(|header| header[..2] == magic && MessageID::try_from(u16::from_be_bytes([header[2], header[3]])).is_ok());
+    match found {
+        Some(start) => buf.advance(start),
+        None => buf.advance(buf.len().saturating_sub(3)),
+    }
+    found.is_some()
+}
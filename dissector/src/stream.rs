@@ -0,0 +1,93 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+use bytes::BytesMut;
+
+/// Bytes held for a gap before it is given up on, e.g. because the capture dropped a packet
+pub const MAX_PENDING_BYTES: usize = 1 << 20;
+/// Segments held for a gap before it is given up on
+pub const MAX_PENDING_SEGMENTS: usize = 1024;
+
+/// One direction of a TCP connection, reassembled in sequence order.
+/// Retransmitted bytes are dropped and segments that arrive early are held until the gap is filled.
+/// A gap that is not filled before [`MAX_PENDING_BYTES`] or [`MAX_PENDING_SEGMENTS`] is reached is skipped,
+/// see [`HalfStream::take_gap`].
+#[derive(Default)]
+pub struct HalfStream {
+    next_seq: Option<u32>,
+    pending: Vec<(u32, Vec<u8>)>,
+    pending_bytes: usize,
+    gap: u64,
+    pub buf: BytesMut,
+}
+
+impl HalfStream {
+    pub fn syn(&mut self, seq: u32) {
+        self.next_seq = Some(seq.wrapping_add(1));
+    }
+
+    /// Add a segment, returns true if new in-order bytes were appended to `buf`
+    pub fn segment(&mut self, seq: u32, payload: &[u8]) -> bool {
+        if payload.is_empty() {
+            return false;
+        }
+        // Capture started mid-connection, take the first segment as the start of the stream
+        let next_seq = *self.next_seq.get_or_insert(seq);
+        if (seq.wrapping_sub(next_seq) as i32) > 0 {
+            self.pending.push((seq, payload.to_vec()));
+            self.pending_bytes += payload.len();
+            if self.pending.len() <= MAX_PENDING_SEGMENTS && self.pending_bytes <= MAX_PENDING_BYTES {
+                return false;
+            }
+            self.skip_gap(next_seq);
+            return self.append_pending();
+        }
+        let appended = self.append(seq, payload);
+        self.append_pending() || appended
+    }
+
+    /// Bytes skipped over since the last call, `None` if the stream had no gap.
+    /// Bytes buffered before a gap end in the middle of it and were dropped with it.
+    pub fn take_gap(&mut self) -> Option<u64> {
+        match std::mem::take(&mut self.gap) {
+            0 => None,
+            gap => Some(gap),
+        }
+    }
+
+    /// Give up on the bytes missing before the earliest pending segment
+    fn skip_gap(&mut self, next_seq: u32) {
+        let Some(lowest) = self.pending.iter().map(|(seq, _)| *seq).min_by_key(|seq| seq.wrapping_sub(next_seq)) else {
+            return;
+        };
+        self.gap += u64::from(lowest.wrapping_sub(next_seq));
+        self.next_seq = Some(lowest);
+        self.buf.clear();
+    }
+
+    /// Append the pending segments the stream has caught up with
+    fn append_pending(&mut self) -> bool {
+        let mut appended = false;
+        while let Some(i) = self.pending.iter().position(|(seq, _)| (seq.wrapping_sub(self.next_seq.unwrap_or(*seq)) as i32) <= 0) {
+            let (seq, payload) = self.pending.swap_remove(i);
+            self.pending_bytes -= payload.len();
+            appended |= self.append(seq, &payload);
+        }
+        appended
+    }
+
+    fn append(&mut self, seq: u32, payload: &[u8]) -> bool {
+        let next_seq = self.next_seq.unwrap_or(seq);
+        let overlap = next_seq.wrapping_sub(seq) as usize;
+        if overlap >= payload.len() {
+            return false;
+        }
+        self.buf.extend_from_slice(&payload[overlap..]);
+        self.next_seq = Some(seq.wrapping_add(payload.len() as u32));
+        true
+    }
+}
@@ -0,0 +1,170 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+//! Builders for small handcrafted captures, checksums are left zero as the dissector ignores them
+
+#![allow(dead_code)]
+
+use std::{net::{IpAddr, SocketAddr}, time::Duration};
+
+pub const LINKTYPE_ETHERNET: u32 = 1;
+pub const LINKTYPE_RAW: u32 = 101;
+
+pub const SYN: u8 = 0x02;
+pub const ACK: u8 = 0x10;
+pub const PSH_ACK: u8 = 0x18;
+
+pub fn addr(addr: &str) -> SocketAddr {
+    addr.parse().unwrap()
+}
+
+/// IP header for `src` to `dst` followed by `transport`
+fn ip(src: IpAddr, dst: IpAddr, protocol: u8, transport: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::new();
+    match (src, dst) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            packet.extend_from_slice(&[0x45, 0]);
+            packet.extend_from_slice(&(20 + transport.len() as u16).to_be_bytes());
+            packet.extend_from_slice(&[0, 0, 0x40, 0, 64, protocol, 0, 0]);
+            packet.extend_from_slice(&src.octets());
+            packet.extend_from_slice(&dst.octets());
+        },
+        (IpAddr::V6(src), IpAddr::V6(dst)) => {
+            packet.extend_from_slice(&[0x60, 0, 0, 0]);
+            packet.extend_from_slice(&(transport.len() as u16).to_be_bytes());
+            packet.extend_from_slice(&[protocol, 64]);
+            packet.extend_from_slice(&src.octets());
+            packet.extend_from_slice(&dst.octets());
+        },
+        _ => panic!("mixed address families"),
+    }
+    packet.extend_from_slice(transport);
+    packet
+}
+
+/// Raw IP packet carrying a TCP segment
+pub fn tcp(src: SocketAddr, dst: SocketAddr, seq: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+    let mut segment = Vec::new();
+    segment.extend_from_slice(&src.port().to_be_bytes());
+    segment.extend_from_slice(&dst.port().to_be_bytes());
+    segment.extend_from_slice(&seq.to_be_bytes());
+    segment.extend_from_slice(&[0, 0, 0, 0, 0x50, flags, 0xff, 0xff, 0, 0, 0, 0]);
+    segment.extend_from_slice(payload);
+    ip(src.ip(), dst.ip(), 6, &segment)
+}
+
+/// Raw IP packet carrying a UDP datagram
+pub fn udp(src: SocketAddr, dst: SocketAddr, payload: &[u8]) -> Vec<u8> {
+    let mut datagram = Vec::new();
+    datagram.extend_from_slice(&src.port().to_be_bytes());
+    datagram.extend_from_slice(&dst.port().to_be_bytes());
+    datagram.extend_from_slice(&(8 + payload.len() as u16).to_be_bytes());
+    datagram.extend_from_slice(&[0, 0]);
+    datagram.extend_from_slice(payload);
+    ip(src.ip(), dst.ip(), 17, &datagram)
+}
+
+/// Ethernet frame around a raw IP packet, with an optional 802.1Q tag
+pub fn ethernet(packet: &[u8], vlan: Option<u16>) -> Vec<u8> {
+    let mut frame = vec![0x02, 0, 0, 0, 0, 1, 0x02, 0, 0, 0, 0, 2];
+    if let Some(vlan) = vlan {
+        frame.extend_from_slice(&[0x81, 0x00]);
+        frame.extend_from_slice(&vlan.to_be_bytes());
+    }
+    frame.extend_from_slice(if packet[0] >> 4 == 4 { &[0x08, 0x00] } else { &[0x86, 0xdd] });
+    frame.extend_from_slice(packet);
+    frame
+}
+
+/// Classic little endian pcap file with microsecond timestamps
+pub fn pcap(linktype: u32, packets: &[(Duration, Vec<u8>)]) -> Vec<u8> {
+    let mut file = Vec::new();
+    file.extend_from_slice(&0xa1b2c3d4u32.to_le_bytes());
+    file.extend_from_slice(&2u16.to_le_bytes());
+    file.extend_from_slice(&4u16.to_le_bytes());
+    file.extend_from_slice(&[0; 8]);
+    file.extend_from_slice(&65535u32.to_le_bytes());
+    file.extend_from_slice(&linktype.to_le_bytes());
+    for (timestamp, data) in packets {
+        file.extend_from_slice(&(timestamp.as_secs() as u32).to_le_bytes());
+        file.extend_from_slice(&timestamp.subsec_micros().to_le_bytes());
+        file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        file.extend_from_slice(data);
+    }
+    file
+}
+
+/// Raw IP packets of a whole capture, timestamped one millisecond apart
+pub fn raw_pcap(packets: Vec<Vec<u8>>) -> Vec<u8> {
+    let packets: Vec<_> = packets.into_iter().enumerate().map(|(i, packet)| (Duration::from_millis(1_700_000_000_000 + i as u64), packet)).collect();
+    pcap(LINKTYPE_RAW, &packets)
+}
+
+fn pcapng_block(file: &mut Vec<u8>, block_type: u32, body: &[u8]) {
+    let padded = (body.len() + 3) & !3;
+    let len = (12 + padded) as u32;
+    file.extend_from_slice(&block_type.to_le_bytes());
+    file.extend_from_slice(&len.to_le_bytes());
+    file.extend_from_slice(body);
+    file.resize(file.len() + padded - body.len(), 0);
+    file.extend_from_slice(&len.to_le_bytes());
+}
+
+/// Little endian pcapng file with one interface, `tsresol` is its `if_tsresol` option
+pub fn pcapng(linktype: u16, tsresol: Option<u8>, packets: &[(u64, Vec<u8>)]) -> Vec<u8> {
+    let mut file = Vec::new();
+    let mut shb = Vec::new();
+    shb.extend_from_slice(&0x1a2b3c4du32.to_le_bytes());
+    shb.extend_from_slice(&1u16.to_le_bytes());
+    shb.extend_from_slice(&0u16.to_le_bytes());
+    shb.extend_from_slice(&u64::MAX.to_le_bytes());
+    pcapng_block(&mut file, 0x0a0d0d0a, &shb);
+
+    let mut idb = Vec::new();
+    idb.extend_from_slice(&linktype.to_le_bytes());
+    idb.extend_from_slice(&[0, 0]);
+    idb.extend_from_slice(&65535u32.to_le_bytes());
+    if let Some(tsresol) = tsresol {
+        idb.extend_from_slice(&9u16.to_le_bytes());
+        idb.extend_from_slice(&1u16.to_le_bytes());
+        idb.extend_from_slice(&[tsresol, 0, 0, 0]);
+        idb.extend_from_slice(&[0; 4]);
+    }
+    pcapng_block(&mut file, 1, &idb);
+
+    for (ticks, data) in packets {
+        let mut epb = Vec::new();
+        epb.extend_from_slice(&0u32.to_le_bytes());
+        epb.extend_from_slice(&((ticks >> 32) as u32).to_le_bytes());
+        epb.extend_from_slice(&(*ticks as u32).to_le_bytes());
+        epb.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        epb.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        epb.extend_from_slice(data);
+        pcapng_block(&mut file, 6, &epb);
+    }
+    file
+}
+
+/// Frame of a message with a `msg_id` and `body`
+pub fn frame(msg_id: u16, body: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x52, 0x43];
+    frame.extend_from_slice(&msg_id.to_be_bytes());
+    frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    frame.extend_from_slice(body);
+    frame
+}
+
+/// IPv4 announcement datagram payload
+pub fn announcement(server: [u8; 4], port: u16, key: u32) -> Vec<u8> {
+    let mut payload = vec![0x52, 0x43, 0, 0];
+    payload.extend_from_slice(&server);
+    payload.extend_from_slice(&port.to_be_bytes());
+    payload.extend_from_slice(&[0, 0]);
+    payload.extend_from_slice(&key.to_be_bytes());
+    payload
+}
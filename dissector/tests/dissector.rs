@@ -0,0 +1,185 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+mod common;
+
+use std::net::SocketAddr;
+
+use common::*;
+use dissector::{pcap, Dissector};
+use wire::{Direction, Message, Transcript};
+
+const CLIENT: &str = "10.0.0.1:40000";
+const SERVER: &str = "10.0.0.2:5050";
+
+fn dissect(file: &[u8], ports: &[u16]) -> String {
+    let mut dissector = Dissector::new();
+    for port in ports {
+        dissector.add_port(*port);
+    }
+    for packet in &pcap::packets(file).unwrap() {
+        dissector.packet(packet);
+    }
+    let mut out = Vec::new();
+    dissector.write(&mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+fn server_greet() -> Vec<u8> {
+    frame(0x8001, &[0])
+}
+
+fn client_greet() -> Vec<u8> {
+    frame(0x0001, &[0, 0, 0, 0, 0x12, 0x34, 0x56, 0x78])
+}
+
+fn add_record(recid: u32, atype: u8, name: &[u8]) -> Vec<u8> {
+    let mut body = recid.to_be_bytes().to_vec();
+    body.extend_from_slice(&[atype, 2]);
+    body.extend_from_slice(&(name.len() as u16).to_be_bytes());
+    body.extend_from_slice(b"ai");
+    body.extend_from_slice(name);
+    frame(0x0003, &body)
+}
+
+fn upload_done() -> Vec<u8> {
+    frame(0x0005, &[0; 4])
+}
+
+/// Handshake, greetings and upload of one connection from `client`
+fn connection(client: SocketAddr, client_isn: u32, server_isn: u32, upload: &[u8]) -> Vec<Vec<u8>> {
+    let server = addr(SERVER);
+    let greet = server_greet();
+    let mut client_data = client_greet();
+    client_data.extend_from_slice(upload);
+    vec![
+        tcp(client, server, client_isn, SYN, b""),
+        tcp(server, client, server_isn, SYN | ACK, b""),
+        tcp(client, server, client_isn + 1, ACK, b""),
+        tcp(server, client, server_isn + 1, PSH_ACK, &greet),
+        tcp(client, server, client_isn + 1, PSH_ACK, &client_data),
+    ]
+}
+
+fn sessions(out: &str) -> usize {
+    out.lines().filter(|line| line.starts_with("# Session ")).count()
+}
+
+#[test]
+fn session_after_announcement() {
+    let mut packets = vec![udp(addr("10.0.0.2:45000"), addr("255.255.255.255:5049"), &announcement([255; 4], 5050, 0x12345678))];
+    let mut upload = add_record(100, 0, b"DEV:TEMP");
+    upload.extend(add_record(100, 1, b"DEV:TEMP:ALIAS"));
+    upload.extend(upload_done());
+    packets.extend(connection(addr(CLIENT), 1000, 5000, &upload));
+    let out = dissect(&raw_pcap(packets), &[]);
+
+    assert!(out.contains("server=10.0.0.2:5050 key=0x12345678"), "{}", out);
+    assert_eq!(sessions(&out), 1, "{}", out);
+    assert!(out.contains("# Session 10.0.0.1:40000 -> 10.0.0.2:5050"), "{}", out);
+    for msg in ["ServerGreet", "ClientGreet", "DEV:TEMP", "DEV:TEMP:ALIAS", "UploadDone"] {
+        assert!(out.contains(msg), "{} missing from {}", msg, out);
+    }
+    assert!(!out.contains("violation"), "{}", out);
+}
+
+#[test]
+fn server_detected_without_announcement() {
+    // The RecCeiver speaks first, only its ServerGreet shows which end it is
+    let server = addr(SERVER);
+    let client = addr(CLIENT);
+    let packets = vec![
+        tcp(server, client, 5001, PSH_ACK, &server_greet()),
+        tcp(client, server, 1001, PSH_ACK, &client_greet()),
+    ];
+    let out = dissect(&raw_pcap(packets), &[]);
+    assert!(out.contains("Session 10.0.0.1:40000 -> 10.0.0.2:5050"), "{}", out);
+    assert!(!out.contains("violation"), "{}", out);
+}
+
+#[test]
+fn server_port_given() {
+    // Decode errors are shown for connections to a known RecCeiver port
+    let packets = vec![tcp(addr(CLIENT), addr(SERVER), 1001, PSH_ACK, b"GET / HTTP/1.1\r\n")];
+    let out = dissect(&raw_pcap(packets.clone()), &[5050]);
+    assert!(out.contains("decode error"), "{}", out);
+
+    // and other traffic is ignored
+    let out = dissect(&raw_pcap(packets), &[]);
+    assert_eq!(sessions(&out), 0, "{}", out);
+}
+
+#[test]
+fn reconnect_from_the_same_port() {
+    let mut packets = connection(addr(CLIENT), 1000, 5000, &add_record(100, 0, b"FIRST"));
+    // Same 4-tuple, new sequence numbers and a fresh handshake
+    let mut upload = add_record(100, 0, b"SECOND");
+    upload.extend(upload_done());
+    packets.extend(connection(addr(CLIENT), 900_000, 700_000, &upload));
+    let out = dissect(&raw_pcap(packets), &[5050]);
+
+    assert_eq!(sessions(&out), 2, "{}", out);
+    assert!(out.contains("FIRST") && out.contains("SECOND"), "{}", out);
+    assert_eq!(out.matches("ClientGreet").count(), 2, "{}", out);
+    assert!(!out.contains("violation"), "{}", out);
+}
+
+#[test]
+fn output_is_a_transcript() {
+    let mut upload = add_record(100, 0, b"DEV:TEMP");
+    upload.extend(add_record(100, 1, b"DEV:TEMP:ALIAS"));
+    upload.extend(upload_done());
+    let out = dissect(&raw_pcap(connection(addr(CLIENT), 1000, 5000, &upload)), &[]);
+
+    let transcript: Transcript = out.parse().unwrap_or_else(|err| panic!("{}: {}", err, out));
+    let msg_ids: Vec<u16> = transcript.entries.iter().map(|(_, msg)| msg.msg_id()).collect();
+    assert_eq!(msg_ids, [0x8001, 0x0001, 0x0003, 0x0003, 0x0005]);
+    assert_eq!(transcript.entries[0].0, Direction::ServerToClient);
+    let Message::ClientGreet(greet) = &transcript.entries[1].1 else {
+        panic!("not a ClientGreet: {}", out);
+    };
+    assert_eq!(greet.serv_key, 0x12345678);
+    for (direction, msg) in &transcript.entries {
+        assert!(out.contains(&format!("{} {}  #", direction, msg)), "{} {} missing from {}", direction, msg, out);
+    }
+}
+
+#[test]
+fn violations_are_comments() {
+    // DelRecord before the greetings breaks the session order
+    let server = addr(SERVER);
+    let client = addr(CLIENT);
+    let packets = vec![tcp(client, server, 1001, PSH_ACK, &frame(0x0004, &100u32.to_be_bytes()))];
+    let out = dissect(&raw_pcap(packets), &[5050]);
+    assert!(out.contains("protocol violation"), "{}", out);
+    let transcript: Transcript = out.parse().unwrap_or_else(|err| panic!("{}: {}", err, out));
+    assert_eq!(transcript.entries.len(), 1);
+}
+
+#[test]
+fn lost_segment_is_skipped() {
+    let record = add_record(100, 0, b"DEV:TEMP");
+    let mut packets = connection(addr(CLIENT), 1000, 5000, &record[..6]);
+    // The capture lost the next 10 bytes, the rest of the record arrives with enough messages after it to give up on the gap
+    let mut seq = 1001 + client_greet().len() as u32 + 16;
+    packets.push(tcp(addr(CLIENT), addr(SERVER), seq, PSH_ACK, &record[16..]));
+    seq += (record.len() - 16) as u32;
+    for recid in 0..dissector::stream::MAX_PENDING_SEGMENTS as u32 {
+        let del = frame(0x0004, &recid.to_be_bytes());
+        packets.push(tcp(addr(CLIENT), addr(SERVER), seq, PSH_ACK, &del));
+        seq += del.len() as u32;
+    }
+    let out = dissect(&raw_pcap(packets), &[]);
+
+    assert!(out.contains("C->S gap of 10 bytes"), "{}", out);
+    assert!(!out.contains("DEV:TEMP"), "{}", out);
+    assert!(!out.contains("decode error"), "{}", out);
+    let transcript: Transcript = out.parse().unwrap_or_else(|err| panic!("{}: {}", err, out));
+    let msg_ids: Vec<u16> = transcript.entries.iter().map(|(_, msg)| msg.msg_id()).collect();
+    assert_eq!(msg_ids.len(), 2 + dissector::stream::MAX_PENDING_SEGMENTS);
+    assert!(msg_ids[2..].iter().all(|msg_id| *msg_id == 0x0004));
+}
@@ -0,0 +1,125 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+mod common;
+
+use std::time::Duration;
+
+use common::*;
+use dissector::{packet::{self, Transport}, pcap};
+
+#[test]
+fn classic_pcap() {
+    let packet = tcp(addr("10.0.0.1:40000"), addr("10.0.0.2:5050"), 1, PSH_ACK, b"RC");
+    let file = pcap(LINKTYPE_RAW, &[(Duration::new(1_700_000_000, 250_000), packet.clone()), (Duration::new(1_700_000_001, 0), packet.clone())]);
+    let packets = pcap::packets(&file).unwrap();
+    assert_eq!(packets.len(), 2);
+    assert_eq!(packets[0].timestamp, Duration::new(1_700_000_000, 250_000));
+    assert_eq!(packets[0].linktype, LINKTYPE_RAW as u16);
+    assert_eq!(packets[0].data, &packet[..]);
+    assert_eq!(packets[1].timestamp, Duration::new(1_700_000_001, 0));
+}
+
+#[test]
+fn classic_pcap_big_endian_nanoseconds() {
+    let mut file = vec![0xa1, 0xb2, 0x3c, 0x4d, 0, 2, 0, 4];
+    file.extend_from_slice(&[0; 8]);
+    file.extend_from_slice(&65535u32.to_be_bytes());
+    file.extend_from_slice(&LINKTYPE_ETHERNET.to_be_bytes());
+    file.extend_from_slice(&7u32.to_be_bytes());
+    file.extend_from_slice(&123_456_789u32.to_be_bytes());
+    file.extend_from_slice(&3u32.to_be_bytes());
+    file.extend_from_slice(&3u32.to_be_bytes());
+    file.extend_from_slice(&[1, 2, 3]);
+
+    let packets = pcap::packets(&file).unwrap();
+    assert_eq!(packets.len(), 1);
+    assert_eq!(packets[0].timestamp, Duration::new(7, 123_456_789));
+    assert_eq!(packets[0].linktype, LINKTYPE_ETHERNET as u16);
+    assert_eq!(packets[0].data, &[1, 2, 3]);
+}
+
+#[test]
+fn pcapng_timestamp_resolution() {
+    let packet = udp(addr("10.0.0.2:5050"), addr("255.255.255.255:5049"), &announcement([0; 4], 5050, 1));
+    // Microseconds unless the interface says otherwise
+    let file = pcapng(LINKTYPE_RAW as u16, None, &[(1_500_000, packet.clone())]);
+    let packets = pcap::packets(&file).unwrap();
+    assert_eq!(packets.len(), 1);
+    assert_eq!(packets[0].timestamp, Duration::from_millis(1500));
+    assert_eq!(packets[0].data, &packet[..]);
+
+    let file = pcapng(LINKTYPE_RAW as u16, Some(9), &[(1_500_000_001, packet.clone())]);
+    assert_eq!(pcap::packets(&file).unwrap()[0].timestamp, Duration::new(1, 500_000_001));
+}
+
+#[test]
+fn malformed_captures() {
+    assert!(pcap::packets(b"RC").is_err());
+    assert!(pcap::packets(b"not a capture file").is_err());
+
+    let file = pcap(LINKTYPE_RAW, &[(Duration::ZERO, vec![0x45; 40])]);
+    assert!(pcap::packets(&file[..20]).is_err());
+    assert!(pcap::packets(&file[..file.len() - 1]).is_err());
+
+    let file = pcapng(LINKTYPE_RAW as u16, None, &[(0, vec![0x45; 40])]);
+    assert!(pcap::packets(&file[..file.len() - 4]).is_err());
+}
+
+#[test]
+fn ethernet_vlan_tcp() {
+    let ip = tcp(addr("10.0.0.1:40000"), addr("10.0.0.2:5050"), 0xdeadbeef, PSH_ACK, b"payload");
+    let frame = ethernet(&ip, Some(42));
+    let Some(Transport::Tcp(segment)) = packet::parse(LINKTYPE_ETHERNET as u16, &frame) else {
+        panic!("not a TCP segment");
+    };
+    assert_eq!(segment.src, addr("10.0.0.1:40000"));
+    assert_eq!(segment.dst, addr("10.0.0.2:5050"));
+    assert_eq!(segment.seq, 0xdeadbeef);
+    assert!(segment.ack && !segment.syn);
+    assert_eq!(segment.payload, b"payload");
+}
+
+#[test]
+fn ipv6_udp() {
+    let frame = ethernet(&udp(addr("[fe80::1]:5050"), addr("[ff02::1]:5049"), b"datagram"), None);
+    let Some(Transport::Udp(datagram)) = packet::parse(LINKTYPE_ETHERNET as u16, &frame) else {
+        panic!("not a UDP datagram");
+    };
+    assert_eq!(datagram.src, addr("[fe80::1]:5050"));
+    assert_eq!(datagram.dst, addr("[ff02::1]:5049"));
+    assert_eq!(datagram.payload, b"datagram");
+}
+
+#[test]
+fn truncated_headers() {
+    // Cut anywhere in the link, IP or TCP header
+    let frame = ethernet(&tcp(addr("10.0.0.1:40000"), addr("10.0.0.2:5050"), 1, ACK, b""), None);
+    assert_eq!(frame.len(), 14 + 20 + 20);
+    for len in 0..frame.len() {
+        assert!(packet::parse(LINKTYPE_ETHERNET as u16, &frame[..len]).is_none(), "parsed {} bytes", len);
+    }
+    assert!(packet::parse(LINKTYPE_ETHERNET as u16, &frame).is_some());
+
+    let frame = udp(addr("[::1]:5050"), addr("[::1]:5049"), b"");
+    for len in 0..frame.len() {
+        assert!(packet::parse(LINKTYPE_RAW as u16, &frame[..len]).is_none(), "parsed {} bytes", len);
+    }
+}
+
+#[test]
+fn fragments_and_other_protocols_are_skipped() {
+    let mut fragment = tcp(addr("10.0.0.1:40000"), addr("10.0.0.2:5050"), 1, ACK, b"data");
+    // More fragments flag
+    fragment[6] = 0x20;
+    assert!(packet::parse(LINKTYPE_RAW as u16, &fragment).is_none());
+
+    let mut icmp = udp(addr("10.0.0.1:0"), addr("10.0.0.2:0"), b"ping");
+    icmp[9] = 1;
+    assert!(packet::parse(LINKTYPE_RAW as u16, &icmp).is_none());
+    assert!(packet::parse(9999, &icmp).is_none());
+}
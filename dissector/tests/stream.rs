@@ -0,0 +1,88 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+use dissector::stream::{HalfStream, MAX_PENDING_BYTES, MAX_PENDING_SEGMENTS};
+
+#[test]
+fn in_order() {
+    let mut stream = HalfStream::default();
+    stream.syn(1000);
+    assert!(stream.segment(1001, b"hello "));
+    assert!(stream.segment(1007, b"world"));
+    assert_eq!(&stream.buf[..], b"hello world");
+}
+
+#[test]
+fn out_of_order_segments_wait_for_the_gap() {
+    let mut stream = HalfStream::default();
+    stream.syn(1000);
+    assert!(!stream.segment(1012, b"!"));
+    assert!(!stream.segment(1007, b"world"));
+    assert!(stream.buf.is_empty());
+    assert!(stream.segment(1001, b"hello "));
+    assert_eq!(&stream.buf[..], b"hello world!");
+}
+
+#[test]
+fn retransmissions_are_dropped() {
+    let mut stream = HalfStream::default();
+    stream.syn(1000);
+    assert!(stream.segment(1001, b"hello "));
+    assert!(!stream.segment(1001, b"hello "));
+    // Overlaps the end of the stream, only the new bytes are kept
+    assert!(stream.segment(1004, b"lo world"));
+    assert_eq!(&stream.buf[..], b"hello world");
+}
+
+#[test]
+fn sequence_numbers_wrap() {
+    let mut stream = HalfStream::default();
+    stream.syn(u32::MAX - 3);
+    assert!(!stream.segment(3, b"world"));
+    assert!(stream.segment(u32::MAX - 2, b"hello "));
+    assert_eq!(&stream.buf[..], b"hello world");
+}
+
+#[test]
+fn capture_started_mid_connection() {
+    let mut stream = HalfStream::default();
+    assert!(stream.segment(5000, b"first"));
+    assert!(stream.segment(5005, b" second"));
+    assert_eq!(&stream.buf[..], b"first second");
+}
+
+#[test]
+fn unfilled_gap_is_skipped() {
+    let mut stream = HalfStream::default();
+    stream.syn(1000);
+    assert!(stream.segment(1001, b"hel"));
+    // "lo " never arrives
+    let mut seq = 1007;
+    for _ in 0..MAX_PENDING_SEGMENTS {
+        assert!(!stream.segment(seq, b"w"));
+        seq += 1;
+    }
+    assert_eq!(stream.take_gap(), None);
+    assert!(stream.segment(seq, b"!"));
+    // The bytes before the gap cannot be completed and are dropped
+    assert_eq!(stream.buf.len(), MAX_PENDING_SEGMENTS + 1);
+    assert_eq!(stream.take_gap(), Some(3));
+    assert_eq!(stream.take_gap(), None);
+}
+
+#[test]
+fn large_gap_is_skipped() {
+    let mut stream = HalfStream::default();
+    stream.syn(1000);
+    let payload = vec![0; MAX_PENDING_BYTES / 2 + 1];
+    assert!(!stream.segment(2001, &payload));
+    assert!(stream.segment(2001 + payload.len() as u32, &payload));
+    assert_eq!(stream.buf.len(), 2 * payload.len());
+    assert_eq!(stream.take_gap(), Some(1000));
+    // The stream continues after the skipped gap
+    assert!(stream.segment(2001 + 2 * payload.len() as u32, b"next"));
+}
@@ -5,10 +5,10 @@
 // You must comply with both licenses to use, modify, or distribute this software.
 // See the LICENSE file for details.
 
-use reccaster::{record::Record, Reccaster};
+use reccaster::{record::Record, Reccaster, ReccasterError};
 
 #[tokio::main]
-async fn main() {
+async fn main() -> Result<(), ReccasterError> {
 
     tracing_subscriber::fmt().init();
 
@@ -16,6 +16,6 @@ async fn main() {
     record.properties.insert("recordDesc".to_string(), "Rust Recaster".to_string());
     let records: Vec<Record> = vec![record];
 
-    let mut caster = Reccaster::new(records).await;
-    caster.run().await;
+    let mut caster = Reccaster::new(records).await?;
+    caster.run().await
 }
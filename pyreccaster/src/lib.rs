@@ -5,11 +5,14 @@
 // You must comply with both licenses to use, modify, or distribute this software.
 // See the LICENSE file for details.
 
+// pyo3 0.20 macros expand to impl blocks that trip this lint on newer compilers
+#![allow(non_local_definitions)]
+
 use std::{collections::HashMap, sync::Arc};
 
-use pyo3::{prelude::*, types::PyDict};
+use pyo3::{exceptions::{PyConnectionError, PyOSError, PyRuntimeError, PyTimeoutError, PyValueError}, prelude::*, types::PyDict};
 use pyo3_asyncio::tokio::future_into_py_with_locals;
-use reccaster::{Record, Reccaster};
+use reccaster::{CancellationToken, Record, Reccaster, ReccasterError};
 use tokio::sync::Mutex;
        
 #[pyclass]
@@ -60,21 +63,36 @@ impl<'source> FromPyObject<'source> for PyRecord {
     }
 }
 
+/// Raise Reccaster errors as the closest built-in Python exception
+fn to_py_err(err: ReccasterError) -> PyErr {
+    let msg = err.to_string();
+    match err {
+        ReccasterError::Bind { .. } | ReccasterError::JoinMulticast { .. } | ReccasterError::Io(_) => PyOSError::new_err(msg),
+        ReccasterError::Connect { .. } | ReccasterError::ConnectionClosed => PyConnectionError::new_err(msg),
+        ReccasterError::Timeout(_) => PyTimeoutError::new_err(msg),
+        ReccasterError::Protocol(_) => PyRuntimeError::new_err(msg),
+        ReccasterError::InvalidRecord { .. } => PyValueError::new_err(msg),
+    }
+}
+
 #[pyclass]
 struct PyReccaster {
     reccaster: Arc<Mutex<Reccaster>>,
+    shutdown: CancellationToken,
 }
 
 #[pymethods]
 impl PyReccaster {
 
     #[staticmethod]
-    fn setup(py: Python, records: Vec<PyRecord>) -> PyResult<&PyAny> {
+    fn setup(py: Python<'_>, records: Vec<PyRecord>) -> PyResult<&PyAny> {
         let locals = pyo3_asyncio::tokio::get_current_locals(py)?;
         let pvs = records.iter().map(|record: &PyRecord| record.0.clone()).collect::<Vec<Record>>();
         future_into_py_with_locals(py, locals.clone(), async move {
-            let recc = Reccaster::new(pvs).await;
-            let pyrecc = PyReccaster { reccaster: Arc::new(Mutex::new(recc)) };
+            let recc = Reccaster::new(pvs).await.map_err(to_py_err)?;
+            // Kept outside the mutex, `run` holds the lock until it returns
+            let shutdown = recc.shutdown_token();
+            let pyrecc = PyReccaster { reccaster: Arc::new(Mutex::new(recc)), shutdown };
             Python::with_gil(|py| Ok(pyrecc.into_py(py)))
         })
     }
@@ -85,10 +103,14 @@ impl PyReccaster {
 
         future_into_py_with_locals(py, locals.clone(), async move {
             let mut recc = recc_arc.lock().await;
-            recc.run().await;
-            Ok(())
+            recc.run().await.map_err(to_py_err)
         })
     }
+
+    /// Make `run` close the connection to the RecCeiver and return
+    fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
 }
 
 #[pymodule]
@@ -0,0 +1,95 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+use std::{net::{Ipv4Addr, Ipv6Addr, SocketAddr}, time::Duration};
+
+/// Where a Reccaster listens for RecCeiver announcements
+#[derive(Debug, Clone)]
+pub struct ReccasterConfig {
+    /// Address the announcement socket binds to.
+    /// Use `[::]:5049` to receive IPv6 announcements, on most systems it receives IPv4 broadcasts too.
+    pub bind_addr: SocketAddr,
+    /// IPv6 multicast groups to join, IPv6 has no broadcast so RecCeivers announce to a group
+    pub multicast_v6: Vec<Ipv6Addr>,
+    /// Interface index used to join the multicast groups, 0 lets the system choose
+    pub multicast_interface: u32,
+    /// How connections to an announced RecCeiver are retried
+    pub backoff: BackoffConfig,
+    /// Send DelRecord for every uploaded record before closing the connection on shutdown,
+    /// otherwise the RecCeiver only marks them inactive when it sees the connection close
+    pub delete_records_on_shutdown: bool,
+    /// Longest wait for the TCP connection to an announced RecCeiver, a timeout counts as a failed attempt
+    pub connect_timeout: Duration,
+    /// Longest wait for the ServerGreet after connecting, a timeout counts as a failed attempt
+    pub greet_timeout: Duration,
+    /// Longest wait for the RecCeiver to take the greeting, a Pong, the upload, or the deletions and close on shutdown,
+    /// before the connection is given up
+    pub write_timeout: Duration,
+}
+
+impl Default for ReccasterConfig {
+    fn default() -> Self {
+        ReccasterConfig {
+            bind_addr: SocketAddr::from((Ipv4Addr::UNSPECIFIED, wire::SERVER_ANNOUNCEMENT_UDP_PORT)),
+            multicast_v6: Vec::new(),
+            multicast_interface: 0,
+            backoff: BackoffConfig::default(),
+            delete_records_on_shutdown: false,
+            connect_timeout: Duration::from_secs(10),
+            greet_timeout: Duration::from_secs(10),
+            write_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl ReccasterConfig {
+    /// Listen on all IPv6 and IPv4 addresses and join the IPv6 multicast `group`
+    pub fn ipv6_multicast(group: Ipv6Addr) -> ReccasterConfig {
+        ReccasterConfig {
+            bind_addr: SocketAddr::from((Ipv6Addr::UNSPECIFIED, wire::SERVER_ANNOUNCEMENT_UDP_PORT)),
+            multicast_v6: vec![group],
+            ..ReccasterConfig::default()
+        }
+    }
+}
+
+/// Exponential backoff between attempts to connect to, greet and upload to a RecCeiver
+#[derive(Debug, Clone)]
+pub struct BackoffConfig {
+    /// Delay before the first retry
+    pub initial_delay: Duration,
+    /// Longest delay between two attempts
+    pub max_delay: Duration,
+    /// Factor the delay grows by after every failed attempt
+    pub multiplier: f64,
+    /// Fraction of the delay, between 0 and 1, taken off at random so restarted casters do not retry in step
+    pub jitter: f64,
+    /// Failed attempts before going back to listening for announcements, `None` retries the same RecCeiver forever
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for BackoffConfig {
+    fn default() -> Self {
+        BackoffConfig {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            multiplier: 2.0,
+            jitter: 0.5,
+            max_attempts: Some(5),
+        }
+    }
+}
+
+impl BackoffConfig {
+    /// Delay before the next attempt, after `failures` failed attempts in a row
+    pub fn delay(&self, failures: u32) -> Duration {
+        let max = self.max_delay.as_secs_f64();
+        let delay = (self.initial_delay.as_secs_f64() * self.multiplier.powi(failures.saturating_sub(1).min(i32::MAX as u32) as i32)).min(max);
+        let jitter = self.jitter.clamp(0.0, 1.0) * fastrand::f64();
+        Duration::try_from_secs_f64(delay * (1.0 - jitter)).unwrap_or(self.max_delay)
+    }
+}
@@ -0,0 +1,63 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+use std::{fmt, io, net::{Ipv6Addr, SocketAddr}, time::Duration};
+
+use wire::WireError;
+
+/// Errors raised by a Reccaster
+#[derive(Debug)]
+pub enum ReccasterError {
+    /// Announcement socket cannot be bound, usually because another process uses the port
+    Bind { addr: SocketAddr, source: io::Error },
+    /// IPv6 multicast group cannot be joined, e.g. because the socket is bound to IPv4 or the interface does not exist
+    JoinMulticast { group: Ipv6Addr, source: io::Error },
+    /// Announcement socket failed while receiving
+    Io(io::Error),
+    /// Announced RecCeiver cannot be connected to
+    Connect { addr: SocketAddr, source: io::Error },
+    /// RecCeiver closed the connection
+    ConnectionClosed,
+    /// RecCeiver did not greet, or take the data written to it, in time
+    Timeout(Duration),
+    /// Message cannot be decoded or sent, or breaks the session order
+    Protocol(WireError),
+    /// Record cannot be uploaded, e.g. its name is too long for the message
+    InvalidRecord { name: String, source: WireError },
+}
+
+impl fmt::Display for ReccasterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReccasterError::Bind { addr, source } => write!(f, "failed to bind announcement socket to {}: {}", addr, source),
+            ReccasterError::JoinMulticast { group, source } => write!(f, "failed to join multicast group {}: {}", group, source),
+            ReccasterError::Io(err) => write!(f, "announcement socket failed: {}", err),
+            ReccasterError::Connect { addr, source } => write!(f, "failed to connect to RecCeiver {}: {}", addr, source),
+            ReccasterError::ConnectionClosed => write!(f, "RecCeiver closed the connection"),
+            ReccasterError::Timeout(timeout) => write!(f, "RecCeiver did not respond within {:?}", timeout),
+            ReccasterError::Protocol(err) => write!(f, "protocol error: {}", err),
+            ReccasterError::InvalidRecord { name, source } => write!(f, "invalid record {}: {}", name, source),
+        }
+    }
+}
+
+impl std::error::Error for ReccasterError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReccasterError::Bind { source, .. } | ReccasterError::JoinMulticast { source, .. } | ReccasterError::Connect { source, .. } => Some(source),
+            ReccasterError::Io(err) => Some(err),
+            ReccasterError::Protocol(err) | ReccasterError::InvalidRecord { source: err, .. } => Some(err),
+            ReccasterError::ConnectionClosed | ReccasterError::Timeout(_) => None,
+        }
+    }
+}
+
+impl From<WireError> for ReccasterError {
+    fn from(err: WireError) -> Self {
+        ReccasterError::Protocol(err)
+    }
+}
@@ -5,200 +5,301 @@
 // You must comply with both licenses to use, modify, or distribute this software.
 // See the LICENSE file for details.
 
+pub mod config;
+pub mod error;
 pub mod record;
+pub use self::config::{BackoffConfig, ReccasterConfig};
+pub use self::error::ReccasterError;
 pub use self::record::Record;
+pub use tokio_util::sync::CancellationToken;
 
-use std::{io, net::{IpAddr, Ipv4Addr, SocketAddr}};
-use tokio::{net::{UdpSocket, TcpStream}, io::Interest}; 
-use tokio_util::codec::Framed;
-use tracing::{debug, error, info};
-use wire::{Announcement, Message, MessageCodec, MSG_MAGIC_ID};
+use std::{io, net::SocketAddr, time::Duration};
+
+use bytes::Bytes;
+use tokio::net::{UdpSocket, TcpStream};
+use tokio_util::{codec::Framed, udp::UdpFramed};
+use tracing::{debug, error, info, warn};
+use wire::{session::SessionChecker, Direction, Message, WireError, AnnouncementCodec, ClientCodec, ClientMessage, ServerMessage, UnknownPolicy, Upload};
 use tokio_stream::StreamExt;
 use futures::SinkExt;
 
 pub struct Reccaster {
-    udpsock: UdpSocket,
-    framed: Option<Framed<TcpStream, MessageCodec>>,
-    buf: [u8; 1024],
-    pvs: Vec<Record>,
+    udpsock: UdpFramed<AnnouncementCodec>,
+    framed: Option<Framed<TcpStream, ClientCodec>>,
+    upload: Upload,
+    session: SessionChecker,
+    backoff: BackoffConfig,
+    delete_records_on_shutdown: bool,
+    connect_timeout: Duration,
+    greet_timeout: Duration,
+    write_timeout: Duration,
+    shutdown: CancellationToken,
     state: CasterState,
 }
 
+/// RecCeiver taken from an announcement
+#[derive(Debug, Copy, Clone)]
+struct Server {
+    addr: SocketAddr,
+    key: u32,
+}
+
 enum CasterState {
     Announcement,
-    Handshake(Announcement),
-    Upload,
+    /// Connecting to the announced RecCeiver, after the given number of failed attempts
+    Handshake(Server, u32),
+    Upload(Server, u32),
     PingPong,
 }
 
 impl Reccaster {
 
-    pub async fn new(records: Vec<Record>) -> Reccaster {
-        let sock = UdpSocket::bind(format!("0.0.0.0:{}", wire::SERVER_ANNOUNCEMENT_UDP_PORT)).await.unwrap();
-        debug!("listening for announcement messages at {}", wire::SERVER_ANNOUNCEMENT_UDP_PORT);
-        Self { udpsock: sock, framed: None, buf: [0; 1024], pvs: records, state: CasterState::Announcement } 
+    pub async fn new(records: Vec<Record>) -> Result<Reccaster, ReccasterError> {
+        Self::with_config(records, ReccasterConfig::default()).await
     }
 
-    pub async fn run(&mut self) {
-        loop {
-            match self.state {
-                CasterState::Announcement => self.handle_announcement().await,
-                CasterState::Handshake(_) => self.handle_handshake().await,
-                CasterState::Upload => self.handle_upload().await,
-                CasterState::PingPong => self.handle_pingpong().await,
-            }
+    pub async fn with_config(records: Vec<Record>, config: ReccasterConfig) -> Result<Reccaster, ReccasterError> {
+        let upload = Self::build_upload(&records)?;
+        let sock = UdpSocket::bind(config.bind_addr).await.map_err(|source| ReccasterError::Bind { addr: config.bind_addr, source })?;
+        for group in &config.multicast_v6 {
+            sock.join_multicast_v6(group, config.multicast_interface).map_err(|source| ReccasterError::JoinMulticast { group: *group, source })?;
+            debug!("joined multicast group {}", group);
         }
+        debug!("listening for announcement messages at {}", config.bind_addr);
+        Ok(Self { udpsock: UdpFramed::new(sock, AnnouncementCodec), framed: None, upload, session: SessionChecker::new(), backoff: config.backoff, delete_records_on_shutdown: config.delete_records_on_shutdown, connect_timeout: config.connect_timeout, greet_timeout: config.greet_timeout, write_timeout: config.write_timeout, shutdown: CancellationToken::new(), state: CasterState::Announcement })
     }
 
-    async fn handle_announcement(&mut self) {
-        let ready = self.udpsock.ready(Interest::READABLE).await.unwrap();
-        if ready.is_readable() {
-            match self.udpsock.try_recv_from(&mut self.buf) {
-                Ok((len, addr)) => {
-                    if len >= 16 {
-                        let msg = Self::parse_announcement_message(&self.buf[..len], addr).unwrap();
-                        info!("Received announcement message: {:?}:{:?} with key:{:?} from: {:?}", msg.server_addr, msg.server_port, msg.server_key, addr);
-                        self.state = CasterState::Handshake(msg);
-                    }
-                },
-                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => { return; },
-                Err(err) => { error!("{:?}", err) }
+    /// Build the AddRecord/AddInfo messages for all records once, so every upload only copies them to the socket buffer
+    fn build_upload(records: &[Record]) -> Result<Upload, ReccasterError> {
+        let mut upload = Upload::new();
+        for (i, record) in records.iter().enumerate() {
+            let recid: u32 = i as u32 + 100;
+            let record_type = Bytes::from(record.r#type.clone());
+            let invalid = |source| ReccasterError::InvalidRecord { name: record.name.clone(), source };
+            // AddRecord Message
+            upload.add_record(recid, wire::AddRecordType::Record, record_type.clone(), record.name.clone()).map_err(invalid)?;
+            // AddRecord alias Message if avaliable
+            if let Some(record_alias) = &record.alias {
+                upload.add_record(recid, wire::AddRecordType::Alias, record_type.clone(), record_alias.clone()).map_err(invalid)?;
             };
+            // AddInfo Message
+            for (key, value) in &record.properties {
+                upload.add_info(recid, key.clone(), value.clone()).map_err(invalid)?;
+            }
         }
+        Ok(upload)
     }
 
-    async fn handle_handshake(&mut self) {
-        if let CasterState::Handshake(msg) = &mut self.state {
-            let addr = msg.server_addr;
-            let port = msg.server_port;
-            let key = msg.server_key;
-            // @TODO handle connection errors 
-            let stream = TcpStream::connect(format!("{}:{}", addr, port)).await.map_err(|err| error!("{:?}",err)).unwrap();
-            info!("connect to {:?}:{}", addr, port);
-            let codec = MessageCodec;
-            let framed = Framed::new(stream, codec);
-            self.framed = Some(framed);
-
-            if let Some(framed) = &mut self.framed {    
-                while let Some(msg) = framed.next().await {
-                    match msg.unwrap() {
-                        Message::ServerGreet(_) => {
-                            let _ = framed.send(Message::ClientGreet(wire::ClientGreet { serv_key: key })).await;
-                            debug!("Greet Message with server key: {}", key);
-                            self.state = CasterState::Upload;
-                            return;
-                        },
-                        _ => {
-                            self.state = CasterState::Announcement;
-                            return;
-                        },
-                    }
-                }
-            }
-        }
+    /// Token that stops [`Reccaster::run`] when cancelled.
+    /// An upload in progress is finished first, then the connection to the RecCeiver is closed and `run` returns.
+    /// The announcement port stays bound until the Reccaster is dropped.
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
     }
 
-    async fn handle_upload(&mut self) {
-        if let CasterState::Upload = &mut self.state {
-            if let Some(framed) = &mut self.framed {
-                for (i, record) in self.pvs.iter().enumerate() {
-                    let recid: u32 = i as u32 + 100; 
-                    // AddRecord Message
-                    let record_name = &record.name;
-                    let record_type = &record.r#type;
-                    let msg = Message::AddRecord(wire::AddRecord { recid: recid, atype: wire::AddRecordType::Record as u8, rtlen: record_type.len() as u8, rnlen: record_name.len() as u16, 
-                        rtype: record_type.to_string(), rname: record_name.to_string() });
-                    let _ = framed.send(msg.clone()).await;
-                    debug!("Sending AddRecord Message: {:?}", msg);
-                    // AddRecord alias Message if avaliable
-                    if let Some(record_alias) = &record.alias {
-                        let msg = Message::AddRecord(wire::AddRecord { recid: recid, atype: wire::AddRecordType::Alias as u8, rtlen: record_type.len() as u8, rnlen: record_alias.len() as u16, 
-                            rtype: record_type.to_string(), rname: record_alias.to_string() });
-                        let _ = framed.send(msg.clone()).await;
+    /// Address the announcement socket is bound to, e.g. to find the port chosen for a `bind_addr` with port 0
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.udpsock.get_ref().local_addr()
+    }
+
+    /// Upload the records to every RecCeiver that announces itself, until the [shutdown token](Reccaster::shutdown_token) is cancelled.
+    /// Connection and protocol errors are logged and retried, only a failing announcement socket is returned.
+    pub async fn run(&mut self) -> Result<(), ReccasterError> {
+        let shutdown = self.shutdown.clone();
+        while !shutdown.is_cancelled() {
+            match &self.state {
+                CasterState::Announcement => tokio::select! {
+                    result = self.handle_announcement() => result?,
+                    _ = shutdown.cancelled() => break,
+                },
+                CasterState::Handshake(server, failures) => {
+                    let (server, failures) = (*server, *failures);
+                    let result = tokio::select! {
+                        result = self.handle_handshake(server, failures) => result,
+                        _ = shutdown.cancelled() => break,
+                    };
+                    if let Err(err) = result {
+                        tokio::select! {
+                            _ = self.retry(server, failures, err) => {},
+                            _ = shutdown.cancelled() => break,
+                        }
+                    }
+                },
+                CasterState::Upload(server, failures) => {
+                    // Not cancelled, so the RecCeiver never sees half an upload, the write timeout bounds it instead
+                    let (server, failures) = (*server, *failures);
+                    if let Err(err) = self.handle_upload().await {
+                        tokio::select! {
+                            _ = self.retry(server, failures, err) => {},
+                            _ = shutdown.cancelled() => break,
+                        }
+                    }
+                },
+                CasterState::PingPong => {
+                    let result = tokio::select! {
+                        result = self.handle_pingpong() => result,
+                        _ = shutdown.cancelled() => break,
                     };
-                    // AddInfo Message
-                    for (key, value) in &record.properties {
-                        let msg = Message::AddInfo(wire::AddInfo { recid: recid, keylen: key.len() as u8, valen: value.len() as u16, key: key.to_string(), value: value.to_string() });
-                        let _ = framed.send(msg.clone()).await;
-                        debug!("Sending AddInfo Message: {:?}", msg.clone());
+                    match result {
+                        Err(ReccasterError::ConnectionClosed) => info!("connection to RecCeiver closed, waiting for announcements"),
+                        Err(err) => error!("session with RecCeiver failed: {}, waiting for announcements", err),
+                        Ok(()) => {},
                     }
-                }
-                let _ = framed.send(Message::UploadDone(wire::UploadDone)).await;
-                debug!("Sending UploadDone Message");
-                self.state = CasterState::PingPong;
+                    self.framed = None;
+                    self.state = CasterState::Announcement;
+                },
             }
         }
+        self.close().await;
+        Ok(())
     }
 
-    async fn handle_pingpong(&mut self) {
-        if let CasterState::PingPong = &mut self.state {
-            if let Some(framed) = &mut self.framed {
-                while let Some(msg_result) = framed.next().await {
-                    match msg_result {
-                        Ok(msg) => {
-                            match msg {
-                                Message::Ping(ping_msg) => {
-                                    info!("received ping with nonce: {}", ping_msg.nonce);
-                                    if let Err(_) = framed.send(Message::Pong(wire::Pong { nonce: ping_msg.nonce })).await {
-                                        self.state = CasterState::Announcement;
-                                        return;
-                                    }
-                                },
-                                _ => {
-                                    self.state = CasterState::Announcement;
-                                    return;
-                                },
-                            }
-                        },
-                        Err(_) => {
-                            self.state = CasterState::Announcement;
-                            return;
-                        }
-                    }
-                } 
-                self.state = CasterState::Announcement;
-                return;
+    /// Close the connection to the RecCeiver, after deleting the uploaded records if configured to
+    async fn close(&mut self) {
+        info!("shutting down");
+        let uploaded = matches!(self.state, CasterState::PingPong);
+        self.state = CasterState::Announcement;
+        let Some(mut framed) = self.framed.take() else {
+            return;
+        };
+        // Aliases share the recid of their record, so only records are deleted
+        let recids: Vec<u32> = if uploaded && self.delete_records_on_shutdown {
+            self.upload.messages().iter().filter_map(|msg| match msg {
+                Message::AddRecord(rec) if rec.atype == wire::AddRecordType::Record as u8 => Some(rec.recid),
+                _ => None,
+            }).collect()
+        } else {
+            Vec::new()
+        };
+        let session = &mut self.session;
+        let result = tokio::time::timeout(self.write_timeout, async {
+            if !recids.is_empty() {
+                debug!("Deleting {} records on shutdown", recids.len());
+            }
+            for recid in recids {
+                let msg = ClientMessage::DelRecord(wire::DelRecord { recid });
+                session.check_client(&msg)?;
+                framed.feed(msg).await?;
             }
+            // Flushes anything still buffered and shuts down the write half
+            framed.close().await?;
+            Ok(())
+        }).await;
+        if let Err(err) = result.unwrap_or(Err(ReccasterError::Timeout(self.write_timeout))) {
+            error!("failed to close connection to RecCeiver: {}", err);
         }
     }
 
-    fn parse_announcement_message(data: &[u8], src_addr: SocketAddr) -> Result<Announcement, &'static str> {
-        let id = u16::from_be_bytes([data[0], data[1]]);
-        // Checking if the ID is 'RC'
-        if id != MSG_MAGIC_ID {             
-            return Err("Invalid ID");
+    async fn handle_announcement(&mut self) -> Result<(), ReccasterError> {
+        match self.udpsock.next().await {
+            Some(Ok((msg, addr))) => {
+                let server = Server { addr: msg.resolve(addr), key: msg.server_key };
+                info!("Received announcement message: {} with key:{:?} from: {:?}", server.addr, server.key, addr);
+                self.state = CasterState::Handshake(server, 0);
+            },
+            Some(Err(WireError::Io(err))) => return Err(ReccasterError::Io(err)),
+            Some(Err(err)) => { error!("failed to decode announcement: {}", err) },
+            None => {},
         }
+        Ok(())
+    }
 
-        let version = data[2];
-        if version != 0 {
-            return Err("Invalid version");
+    /// Drop the connection and wait before trying the RecCeiver again,
+    /// or go back to listening for announcements once the attempts run out
+    async fn retry(&mut self, server: Server, failures: u32, err: ReccasterError) {
+        self.framed = None;
+        let failures = failures + 1;
+        error!("session with RecCeiver {} failed: {}", server.addr, err);
+        if self.backoff.max_attempts.is_some_and(|max| failures >= max) {
+            warn!("giving up on RecCeiver {} after {} attempts, waiting for announcements", server.addr, failures);
+            self.state = CasterState::Announcement;
+            return;
         }
+        let delay = self.backoff.delay(failures);
+        warn!("retrying RecCeiver {} in {:?} (attempt {})", server.addr, delay, failures + 1);
+        tokio::time::sleep(delay).await;
+        self.state = CasterState::Handshake(server, failures);
+    }
+
+    async fn handle_handshake(&mut self, server: Server, failures: u32) -> Result<(), ReccasterError> {
+        let Server { addr, key } = server;
+        let stream = match tokio::time::timeout(self.connect_timeout, TcpStream::connect(addr)).await {
+            Ok(stream) => stream.map_err(|source| ReccasterError::Connect { addr, source })?,
+            Err(_) => return Err(ReccasterError::Connect { addr, source: io::Error::new(io::ErrorKind::TimedOut, format!("no connection within {:?}", self.connect_timeout)) }),
+        };
+        info!("connect to {}", addr);
+        // Step over messages added by newer RecCeivers
+        let mut codec = ClientCodec::new();
+        codec.set_unknown_policy(UnknownPolicy::Skip);
+        let mut framed = Framed::new(stream, codec);
+
+        self.session = SessionChecker::new();
+
+        let greet_timeout = self.greet_timeout;
+        let greeted = tokio::time::timeout(greet_timeout, Self::wait_for_greet(&mut framed, &mut self.session, self.write_timeout)).await;
+        greeted.map_err(|_| ReccasterError::Timeout(greet_timeout))??;
 
-        // Extracting the server address (IPv4, 4 bytes)
-        let server_addr_bytes = &data[4..8];
-        let mut server_addr = Ipv4Addr::new(
-            server_addr_bytes[0],
-            server_addr_bytes[1],
-            server_addr_bytes[2],
-            server_addr_bytes[3],
-        );
-
-        if server_addr.is_broadcast() {
-            match src_addr.ip() {
-                IpAddr::V4(addr) => { server_addr = addr; },
-                IpAddr::V6(_) => { unimplemented!("IPv6 is not supported") },
+        let greet = ClientMessage::ClientGreet(wire::ClientGreet { serv_key: key, ..Default::default() });
+        self.session.check_client(&greet)?;
+        Self::send(&mut framed, greet, self.write_timeout).await?;
+        debug!("Greet Message with server key: {}", key);
+        self.framed = Some(framed);
+        self.state = CasterState::Upload(server, failures);
+        Ok(())
+    }
+
+    /// Read up to the ServerGreet, answering the pings sent before it
+    async fn wait_for_greet(framed: &mut Framed<TcpStream, ClientCodec>, session: &mut SessionChecker, write_timeout: Duration) -> Result<(), ReccasterError> {
+        while let Some(server_msg) = framed.next().await {
+            let server_msg = server_msg?;
+            session.check_server(&server_msg)?;
+            match server_msg {
+                ServerMessage::ServerGreet(_) => return Ok(()),
+                ServerMessage::Ping(ping_msg) => {
+                    info!("received ping with nonce: {} before greeting", ping_msg.nonce);
+                    Self::send(framed, ClientMessage::Pong(wire::Pong { nonce: ping_msg.nonce }), write_timeout).await?;
+                },
+                _ => {},
             }
         }
+        Err(ReccasterError::ConnectionClosed)
+    }
 
-        let server_port = u16::from_be_bytes([data[8], data[9]]);
+    /// Send `msg`, giving up when the RecCeiver does not take it within `timeout`
+    async fn send(framed: &mut Framed<TcpStream, ClientCodec>, msg: ClientMessage, timeout: Duration) -> Result<(), ReccasterError> {
+        tokio::time::timeout(timeout, framed.send(msg)).await.map_err(|_| ReccasterError::Timeout(timeout))??;
+        Ok(())
+    }
 
-        let server_key = u32::from_be_bytes([data[12], data[13], data[14], data[15]]);
+    async fn handle_upload(&mut self) -> Result<(), ReccasterError> {
+        let framed = self.framed.as_mut().ok_or(ReccasterError::ConnectionClosed)?;
+        // The session tracks the uploaded records, so they can be deleted on shutdown
+        for msg in self.upload.messages() {
+            self.session.check(Direction::ClientToServer, msg)?;
+        }
+        self.session.check_client(&ClientMessage::UploadDone(wire::UploadDone))?;
+        // Encode the whole upload straight into the write buffer and send it with a few large writes
+        let buf = framed.write_buffer_mut();
+        buf.reserve(self.upload.encoded_len());
+        self.upload.encode_to(buf);
+        debug!("Sending {} messages and UploadDone, {} bytes", self.upload.messages().len(), self.upload.encoded_len());
+        let timeout = self.write_timeout;
+        tokio::time::timeout(timeout, framed.flush()).await.map_err(|_| ReccasterError::Timeout(timeout))??;
+        self.state = CasterState::PingPong;
+        Ok(())
+    }
 
-        Ok(Announcement {
-            id,
-            server_addr,
-            server_port,
-            server_key,
-        })
+    /// Answer pings until the RecCeiver goes away
+    async fn handle_pingpong(&mut self) -> Result<(), ReccasterError> {
+        let framed = self.framed.as_mut().ok_or(ReccasterError::ConnectionClosed)?;
+        while let Some(msg) = framed.next().await {
+            let msg = msg?;
+            self.session.check_server(&msg)?;
+            if let ServerMessage::Ping(ping_msg) = msg {
+                info!("received ping with nonce: {}", ping_msg.nonce);
+                Self::send(framed, ClientMessage::Pong(wire::Pong { nonce: ping_msg.nonce }), self.write_timeout).await?;
+            }
+        }
+        Err(ReccasterError::ConnectionClosed)
     }
 }
@@ -0,0 +1,54 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+use std::time::Duration;
+
+use reccaster::BackoffConfig;
+
+fn backoff(jitter: f64) -> BackoffConfig {
+    BackoffConfig { initial_delay: Duration::from_millis(100), max_delay: Duration::from_secs(1), multiplier: 2.0, jitter, max_attempts: None }
+}
+
+#[test]
+fn delay_grows_by_the_multiplier() {
+    let backoff = backoff(0.0);
+    assert_eq!(backoff.delay(0), Duration::from_millis(100));
+    assert_eq!(backoff.delay(1), Duration::from_millis(100));
+    assert_eq!(backoff.delay(2), Duration::from_millis(200));
+    assert_eq!(backoff.delay(3), Duration::from_millis(400));
+    assert_eq!(backoff.delay(4), Duration::from_millis(800));
+}
+
+#[test]
+fn delay_is_capped() {
+    let backoff = backoff(0.0);
+    assert_eq!(backoff.delay(5), Duration::from_secs(1));
+    assert_eq!(backoff.delay(1000), Duration::from_secs(1));
+    assert_eq!(backoff.delay(u32::MAX), Duration::from_secs(1));
+
+    let huge = BackoffConfig { multiplier: f64::MAX, ..backoff };
+    assert_eq!(huge.delay(3), Duration::from_secs(1));
+}
+
+#[test]
+fn jitter_only_shortens_the_delay() {
+    let backoff = backoff(0.5);
+    for failures in [1, 3, 10] {
+        let full = self::backoff(0.0).delay(failures);
+        for _ in 0..1000 {
+            let delay = backoff.delay(failures);
+            assert!(delay <= full && delay >= full / 2, "{:?} outside {:?}..={:?}", delay, full / 2, full);
+        }
+    }
+
+    // Out of range jitter is clamped, the delay never goes negative or over the full delay
+    let backoff = self::backoff(3.0);
+    for _ in 0..1000 {
+        assert!(backoff.delay(2) <= Duration::from_millis(200));
+    }
+    assert_eq!(self::backoff(-1.0).delay(2), Duration::from_millis(200));
+}
@@ -0,0 +1,110 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+//! In-process RecCeiver for driving a Reccaster over loopback sockets
+
+#![allow(dead_code)]
+
+use std::{future::Future, net::{Ipv4Addr, SocketAddr}, time::Duration};
+
+use futures::SinkExt;
+use reccaster::{BackoffConfig, Reccaster, ReccasterConfig, ReccasterError, Record};
+use tokio::{net::{TcpListener, TcpStream, UdpSocket}, task::JoinHandle};
+use tokio_stream::StreamExt;
+use tokio_util::codec::Framed;
+use wire::{Announcement, ClientGreet, ClientMessage, ServerCodec, ServerGreet, ServerMessage};
+
+pub const KEY: u32 = 0x12345678;
+
+/// Fail the test instead of hanging when the caster does not do what is expected
+pub async fn within<T>(future: impl Future<Output = T>) -> T {
+    tokio::time::timeout(Duration::from_secs(10), future).await.expect("timed out")
+}
+
+/// Backoff short enough for tests, without jitter
+pub fn fast_backoff(max_attempts: Option<u32>) -> BackoffConfig {
+    BackoffConfig { initial_delay: Duration::from_millis(10), max_delay: Duration::from_millis(50), multiplier: 2.0, jitter: 0.0, max_attempts }
+}
+
+/// Announcements on an ephemeral loopback port
+pub fn config(backoff: BackoffConfig) -> ReccasterConfig {
+    ReccasterConfig { bind_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)), backoff, ..ReccasterConfig::default() }
+}
+
+pub fn record(name: &str) -> Record {
+    Record::new(name.to_string(), "ai".to_string())
+}
+
+/// Caster running in a task, with the address its announcement socket listens on
+pub struct Caster {
+    pub addr: SocketAddr,
+    pub shutdown: reccaster::CancellationToken,
+    pub task: JoinHandle<Result<(), ReccasterError>>,
+}
+
+pub async fn spawn(records: Vec<Record>, config: ReccasterConfig) -> Caster {
+    let mut caster = Reccaster::with_config(records, config).await.unwrap();
+    let addr = caster.local_addr().unwrap();
+    let shutdown = caster.shutdown_token();
+    let task = tokio::spawn(async move { caster.run().await });
+    Caster { addr, shutdown, task }
+}
+
+pub type Connection = Framed<TcpStream, ServerCodec>;
+
+/// RecCeiver listening on an ephemeral loopback port
+pub struct Receiver {
+    pub listener: TcpListener,
+}
+
+impl Receiver {
+    pub async fn bind() -> Receiver {
+        Receiver { listener: TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap() }
+    }
+
+    pub fn port(&self) -> u16 {
+        self.listener.local_addr().unwrap().port()
+    }
+
+    /// Tell the caster at `caster` where to connect
+    pub async fn announce(&self, caster: SocketAddr) {
+        let sock = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let msg = Announcement::new(Ipv4Addr::LOCALHOST, self.port(), KEY);
+        sock.send_to(&msg.encode(), caster).await.unwrap();
+    }
+
+    pub async fn accept(&self) -> Connection {
+        let (stream, _) = within(self.listener.accept()).await.unwrap();
+        Framed::new(stream, ServerCodec::new())
+    }
+}
+
+/// Next message from the caster, `None` once it closed the connection
+pub async fn next(conn: &mut Connection) -> Option<ClientMessage> {
+    within(conn.next()).await.map(|msg| msg.unwrap())
+}
+
+/// Send ServerGreet and wait for the ClientGreet
+pub async fn greet(conn: &mut Connection) -> ClientGreet {
+    conn.send(ServerMessage::ServerGreet(ServerGreet::default())).await.unwrap();
+    match next(conn).await {
+        Some(ClientMessage::ClientGreet(greet)) => greet,
+        other => panic!("expected ClientGreet, got {:?}", other),
+    }
+}
+
+/// Messages of the upload, up to and without UploadDone
+pub async fn upload(conn: &mut Connection) -> Vec<ClientMessage> {
+    let mut msgs = Vec::new();
+    loop {
+        match next(conn).await {
+            Some(ClientMessage::UploadDone(_)) => return msgs,
+            Some(msg) => msgs.push(msg),
+            None => panic!("connection closed during the upload after {} messages", msgs.len()),
+        }
+    }
+}
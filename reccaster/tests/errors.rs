@@ -0,0 +1,78 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+mod common;
+
+use std::{io, net::{Ipv4Addr, Ipv6Addr, UdpSocket}};
+
+use common::*;
+use reccaster::{Reccaster, ReccasterConfig, ReccasterError};
+use wire::WireError;
+
+#[tokio::test]
+async fn taken_port_is_a_bind_error() {
+    let taken = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let bind_addr = taken.local_addr().unwrap();
+    let config = ReccasterConfig { bind_addr, ..ReccasterConfig::default() };
+    match Reccaster::with_config(vec![record("DEV:TEMP")], config).await {
+        Err(ReccasterError::Bind { addr, source }) => {
+            assert_eq!(addr, bind_addr);
+            assert_eq!(source.kind(), io::ErrorKind::AddrInUse);
+        },
+        Err(err) => panic!("expected a bind error, got {}", err),
+        Ok(_) => panic!("bound to a port in use"),
+    }
+}
+
+#[tokio::test]
+async fn failed_multicast_join_is_an_error() {
+    // IPv6 groups cannot be joined on an IPv4 socket
+    let group: Ipv6Addr = "ff02::42:1".parse().unwrap();
+    let config = ReccasterConfig { multicast_v6: vec![group], ..config(fast_backoff(None)) };
+    match Reccaster::with_config(vec![record("DEV:TEMP")], config).await {
+        Err(ReccasterError::JoinMulticast { group: failed, .. }) => assert_eq!(failed, group),
+        Err(err) => panic!("expected a multicast error, got {}", err),
+        Ok(_) => panic!("joined an IPv6 group on an IPv4 socket"),
+    }
+}
+
+async fn invalid_record(record: reccaster::Record) -> (String, WireError) {
+    match Reccaster::with_config(vec![common::record("DEV:OK"), record], config(fast_backoff(None))).await {
+        Err(ReccasterError::InvalidRecord { name, source }) => (name, source),
+        Err(err) => panic!("expected an invalid record, got {}", err),
+        Ok(_) => panic!("record accepted"),
+    }
+}
+
+#[tokio::test]
+async fn oversized_fields_are_invalid_records() {
+    // The record type has a one byte length prefix
+    let mut long_type = record("DEV:TYPE");
+    long_type.r#type = "t".repeat(300);
+    let (name, source) = invalid_record(long_type).await;
+    assert_eq!(name, "DEV:TYPE");
+    assert!(matches!(source, WireError::FieldTooLong { field: "rtype", len: 300, max: 255 }), "{}", source);
+
+    // and so do info keys
+    let mut long_key = record("DEV:KEY");
+    long_key.properties.insert("k".repeat(300), "value".to_string());
+    let (name, source) = invalid_record(long_key).await;
+    assert_eq!(name, "DEV:KEY");
+    assert!(matches!(source, WireError::FieldTooLong { field: "key", len: 300, .. }), "{}", source);
+
+    // Names and aliases have two byte length prefixes, so a 300 byte name is fine but not 70000 bytes
+    let name = "N".repeat(70_000);
+    let (got, source) = invalid_record(record(&name)).await;
+    assert_eq!(got, name);
+    assert!(matches!(source, WireError::FieldTooLong { field: "rname", len: 70_000, max: 65_535 }), "{}", source);
+
+    let mut long_alias = record("DEV:ALIAS");
+    long_alias.alias = Some("A".repeat(70_000));
+    assert_eq!(invalid_record(long_alias).await.0, "DEV:ALIAS");
+
+    Reccaster::with_config(vec![record(&"N".repeat(300))], config(fast_backoff(None))).await.unwrap();
+}
@@ -0,0 +1,109 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+mod common;
+
+use std::{net::Ipv4Addr, time::Duration};
+
+use common::*;
+use reccaster::ReccasterConfig;
+use futures::FutureExt;
+use tokio::net::UdpSocket;
+use wire::{Announcement, ClientMessage};
+
+#[tokio::test]
+async fn greet_failure_is_retried() {
+    let receiver = Receiver::bind().await;
+    let caster = spawn(vec![record("DEV:TEMP")], config(fast_backoff(Some(5)))).await;
+    receiver.announce(caster.addr).await;
+
+    // Closed before the ServerGreet
+    drop(receiver.accept().await);
+    let mut conn = receiver.accept().await;
+    assert_eq!(greet(&mut conn).await.serv_key, KEY);
+    let msgs = upload(&mut conn).await;
+    assert!(matches!(&msgs[..], [ClientMessage::AddRecord(rec)] if rec.rname == "DEV:TEMP"), "{:?}", msgs);
+}
+
+#[tokio::test]
+async fn upload_write_failure_is_retried() {
+    // Far more than the socket buffers hold, so the caster is still writing when the connection is reset
+    let value = "x".repeat(60_000);
+    let records: Vec<_> = (0..300)
+        .map(|i| {
+            let mut record = record(&format!("DEV:{}", i));
+            record.properties.insert("recordDesc".to_string(), value.clone());
+            record
+        })
+        .collect();
+    let receiver = Receiver::bind().await;
+    let caster = spawn(records, config(fast_backoff(Some(5)))).await;
+    receiver.announce(caster.addr).await;
+
+    let mut conn = receiver.accept().await;
+    greet(&mut conn).await;
+    assert!(matches!(next(&mut conn).await, Some(ClientMessage::AddRecord(_))));
+    let stream = conn.into_inner();
+    stream.set_linger(Some(Duration::ZERO)).unwrap();
+    drop(stream);
+
+    let mut conn = receiver.accept().await;
+    greet(&mut conn).await;
+    assert_eq!(upload(&mut conn).await.len(), 600);
+}
+
+#[tokio::test]
+async fn attempts_run_out_and_announcements_restart() {
+    let receiver = Receiver::bind().await;
+    let caster = spawn(vec![record("DEV:TEMP")], config(fast_backoff(Some(3)))).await;
+    receiver.announce(caster.addr).await;
+    for _ in 0..3 {
+        drop(receiver.accept().await);
+    }
+
+    // The caster gave up on the first RecCeiver and takes the next announcement
+    let next_receiver = Receiver::bind().await;
+    next_receiver.announce(caster.addr).await;
+    let mut conn = next_receiver.accept().await;
+    greet(&mut conn).await;
+    assert_eq!(upload(&mut conn).await.len(), 1);
+    // A fourth attempt would have been queued on the first RecCeiver before the caster moved on
+    assert!(receiver.listener.accept().now_or_never().is_none());
+}
+
+#[tokio::test]
+async fn refused_connections_count_as_attempts() {
+    // Nothing listens on the first announced port, every connection to it is refused
+    let closed = Receiver::bind().await;
+    let port = closed.port();
+    drop(closed);
+    let receiver = Receiver::bind().await;
+    let caster = spawn(vec![record("DEV:TEMP")], config(fast_backoff(Some(2)))).await;
+    let sock = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    sock.send_to(&Announcement::new(Ipv4Addr::LOCALHOST, port, KEY).encode(), caster.addr).await.unwrap();
+    receiver.announce(caster.addr).await;
+
+    let mut conn = receiver.accept().await;
+    greet(&mut conn).await;
+    assert_eq!(upload(&mut conn).await.len(), 1);
+    assert!(!caster.task.is_finished());
+}
+
+#[tokio::test]
+async fn missing_greet_is_retried() {
+    let receiver = Receiver::bind().await;
+    let config = ReccasterConfig { greet_timeout: Duration::from_millis(100), ..config(fast_backoff(Some(5))) };
+    let caster = spawn(vec![record("DEV:TEMP")], config).await;
+    receiver.announce(caster.addr).await;
+
+    // Kept open without a ServerGreet, the caster gives up on it and connects again
+    let mut silent = receiver.accept().await;
+    let mut conn = receiver.accept().await;
+    assert!(next(&mut silent).await.is_none());
+    greet(&mut conn).await;
+    assert_eq!(upload(&mut conn).await.len(), 1);
+}
@@ -0,0 +1,113 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+mod common;
+
+use std::time::Duration;
+
+use common::*;
+use futures::SinkExt;
+use reccaster::{BackoffConfig, ReccasterConfig, Record};
+use wire::{ClientMessage, Ping, ServerMessage};
+
+/// Records with an alias and a property large enough that the upload outgrows the socket buffers
+fn large_records(count: usize) -> Vec<Record> {
+    let value = "x".repeat(60_000);
+    (0..count)
+        .map(|i| {
+            let mut record = record(&format!("DEV:{}", i));
+            record.alias = Some(format!("DEV:{}:ALIAS", i));
+            record.properties.insert("recordDesc".to_string(), value.clone());
+            record
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn cancel_while_waiting_for_announcements() {
+    let caster = spawn(vec![record("DEV:TEMP")], config(fast_backoff(None))).await;
+    caster.shutdown.cancel();
+    within(caster.task).await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn upload_finishes_before_records_are_deleted() {
+    let receiver = Receiver::bind().await;
+    let config = ReccasterConfig { delete_records_on_shutdown: true, ..config(fast_backoff(None)) };
+    let caster = spawn(large_records(300), config).await;
+    receiver.announce(caster.addr).await;
+
+    let mut conn = receiver.accept().await;
+    greet(&mut conn).await;
+    // The upload has started and cannot fit in the socket buffers, so it is cancelled halfway through
+    assert!(matches!(next(&mut conn).await, Some(ClientMessage::AddRecord(_))));
+    caster.shutdown.cancel();
+
+    // Record, alias and info for each record, less the one read above
+    assert_eq!(upload(&mut conn).await.len(), 3 * 300 - 1);
+    let mut deleted = Vec::new();
+    while let Some(msg) = next(&mut conn).await {
+        match msg {
+            ClientMessage::DelRecord(msg) => deleted.push(msg.recid),
+            msg => panic!("unexpected {:?} after the upload", msg),
+        }
+    }
+    // Aliases share the recid of their record and are not deleted again
+    assert_eq!(deleted, (100..400).collect::<Vec<_>>());
+    within(caster.task).await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn cancel_established_session() {
+    let receiver = Receiver::bind().await;
+    let caster = spawn(vec![record("DEV:TEMP")], config(fast_backoff(None))).await;
+    receiver.announce(caster.addr).await;
+
+    let mut conn = receiver.accept().await;
+    greet(&mut conn).await;
+    upload(&mut conn).await;
+    conn.send(ServerMessage::Ping(Ping { nonce: 7 })).await.unwrap();
+    assert!(matches!(next(&mut conn).await, Some(ClientMessage::Pong(pong)) if pong.nonce == 7));
+
+    caster.shutdown.cancel();
+    within(caster.task).await.unwrap().unwrap();
+    // Records are left to the RecCeiver unless deleting them is configured
+    assert!(next(&mut conn).await.is_none());
+}
+
+#[tokio::test]
+async fn stalled_upload_times_out() {
+    let receiver = Receiver::bind().await;
+    let config = ReccasterConfig { write_timeout: Duration::from_millis(200), ..config(fast_backoff(None)) };
+    let caster = spawn(large_records(300), config).await;
+    receiver.announce(caster.addr).await;
+
+    // Never read past the greeting, the caster gives up on this connection and connects again
+    let mut stalled = receiver.accept().await;
+    greet(&mut stalled).await;
+    let mut conn = receiver.accept().await;
+    greet(&mut conn).await;
+    assert_eq!(upload(&mut conn).await.len(), 3 * 300);
+    caster.shutdown.cancel();
+    within(caster.task).await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn cancel_during_upload_retry() {
+    let receiver = Receiver::bind().await;
+    let backoff = BackoffConfig { initial_delay: Duration::from_secs(3600), max_delay: Duration::from_secs(3600), ..fast_backoff(None) };
+    let config = ReccasterConfig { write_timeout: Duration::from_millis(100), ..config(backoff) };
+    let caster = spawn(large_records(300), config).await;
+    receiver.announce(caster.addr).await;
+
+    // The upload stalls, times out and the caster waits an hour before the next attempt.
+    // Cancelling before or during that wait both have to return straight away.
+    let mut stalled = receiver.accept().await;
+    greet(&mut stalled).await;
+    caster.shutdown.cancel();
+    within(caster.task).await.unwrap().unwrap();
+}
@@ -0,0 +1,78 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+//! Blocking `std::io` helpers for tools that do not run an async runtime
+
+use bytes::BytesMut;
+use std::{io::{Read, Write}, net::{SocketAddr, ToSocketAddrs, UdpSocket}};
+
+use crate::{header::HEADER_LEN, Announcement, Message, MessageCodec, WireError};
+
+/// Largest datagram read by [`AnnouncementReader`], longer ones are truncated
+const MAX_DATAGRAM_LEN: usize = 1024;
+
+impl MessageCodec {
+    /// Read the next message from a blocking reader, with the same checks as [`MessageCodec::decode_message`].
+    /// Fails with [`WireError::Io`] if the reader ends before a whole frame is read.
+    pub fn read_message(&self, src: &mut impl Read) -> Result<Message, WireError> {
+        loop {
+            let mut header = [0u8; HEADER_LEN];
+            src.read_exact(&mut header)?;
+            let frame_len = self.frame_len(&header)?.expect("a whole header was read");
+
+            let mut frame = BytesMut::zeroed(frame_len);
+            frame[..HEADER_LEN].copy_from_slice(&header);
+            src.read_exact(&mut frame[HEADER_LEN..])?;
+            if let Some(msg) = self.decode_frame(frame.freeze())? {
+                return Ok(msg);
+            }
+        }
+    }
+}
+
+/// Read the next message from a blocking reader with a default [`MessageCodec`]
+pub fn read_message(src: &mut impl Read) -> Result<Message, WireError> {
+    MessageCodec::new().read_message(src)
+}
+
+/// Write a message to a blocking writer, the frame is written with a single `write_all`
+pub fn write_message(dst: &mut impl Write, msg: &Message) -> Result<(), WireError> {
+    let mut buf = Vec::with_capacity(msg.encoded_len());
+    msg.encode_to(&mut buf)?;
+    dst.write_all(&buf)?;
+    Ok(())
+}
+
+/// Blocking reader for UDP announcements
+pub struct AnnouncementReader {
+    socket: UdpSocket,
+}
+
+impl AnnouncementReader {
+    /// Bind a UDP socket to `addr`, usually `("0.0.0.0", SERVER_ANNOUNCEMENT_UDP_PORT)`
+    pub fn bind(addr: impl ToSocketAddrs) -> Result<AnnouncementReader, WireError> {
+        Ok(AnnouncementReader { socket: UdpSocket::bind(addr)? })
+    }
+
+    /// Read announcements from an already configured socket
+    pub fn new(socket: UdpSocket) -> AnnouncementReader {
+        AnnouncementReader { socket }
+    }
+
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// Wait for the next datagram and decode it.
+    /// Returns the announcement as sent with the sender address, see [`Announcement::resolve`].
+    /// A malformed datagram is consumed and reported as an error.
+    pub fn recv(&self) -> Result<(Announcement, SocketAddr), WireError> {
+        let mut buf = [0u8; MAX_DATAGRAM_LEN];
+        let (len, src) = self.socket.recv_from(&mut buf)?;
+        Ok((Announcement::decode(&buf[..len])?, src))
+    }
+}
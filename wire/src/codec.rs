@@ -5,11 +5,10 @@
 // You must comply with both licenses to use, modify, or distribute this software.
 // See the LICENSE file for details.
 
-use bytes::{Buf, BufMut, BytesMut};
-use std::{io, mem::size_of};
-use tokio_util::codec::{Decoder, Encoder};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::borrow::Cow;
 
-use crate::{header::MessageHeader, ClientGreet, Message, MessageID, Ping, Pong, ServerGreet};
+use crate::{header::{MessageHeader, HEADER_LEN}, schema, Message, MessageID, WireError};
 
 /// UDP broadcast port
 pub const SERVER_ANNOUNCEMENT_UDP_PORT: u16 = 5049;
@@ -17,109 +16,205 @@ pub const SERVER_ANNOUNCEMENT_UDP_PORT: u16 = 5049;
 /// Message ID Magic number (ascii "RC")
 pub const MSG_MAGIC_ID: u16 = 0x5243;
 
+/// What the decoder does with frames carrying a message ID it does not know about.
+/// The header length makes it possible to step over them, so newer peers can add message types.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum UnknownPolicy {
+    /// Return the frame as [`Message::Unknown`]
+    #[default]
+    Preserve,
+    /// Drop the frame and decode the next one
+    Skip,
+    /// Fail with [`WireError::UnknownMessageId`]
+    Reject,
+}
+
+/// How the decoder treats record types, names and info tags that are not valid UTF-8.
+/// The C RecCaster sends whatever bytes are in the IOC database.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Utf8Mode {
+    /// Fail with [`WireError::InvalidUtf8`]
+    #[default]
+    Strict,
+    /// Replace invalid sequences with U+FFFD, valid strings are still shared without copying.
+    /// Each replacement takes 3 bytes, so a field can grow past its length limit
+    /// and then fail to encode again with [`WireError::FieldTooLong`].
+    Lossy,
+    /// Keep the exact bytes received
+    Raw,
+}
+
+/// Largest frame a well-behaved peer can send: an AddRecord or AddInfo with a 255 bytes type or key
+/// and a 65535 bytes name or value, plus the header and fixed fields
+pub const DEFAULT_MAX_FRAME_LEN: usize = HEADER_LEN + schema::max_body_len();
+
 /// Encoders and Decoders for Messages
-pub struct MessageCodec;
-
-impl Encoder<Message> for MessageCodec {
-    type Error = io::Error;
-
-    fn encode(&mut self, msg: Message, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        match msg {
-            Message::ClientGreet(msg) => {
-                let header = MessageHeader::new(MessageID::ClientGreet.into(), (size_of::<u32>() + size_of::<ClientGreet>())as u32);
-                dst.put(header.as_bytes());
-                dst.put_u32(0); // Padding
-                dst.put_u32(msg.serv_key);
-                Ok(())
-            },
-            Message::Pong(msg) => {
-                let header = MessageHeader::new(MessageID::Pong as u16, size_of::<Pong>() as u32);
-                dst.put(header.as_bytes());
-                dst.put_u32(msg.nonce);
-                Ok(())
-            },
-            Message::AddRecord(msg) => {
-                let len = (size_of::<u32>() + size_of::<u8>() + size_of::<u8>() + size_of::<u16>() + msg.rtype.len() + msg.rname.len()) as u32;
-                let header = MessageHeader::new(MessageID::AddRecord.into(), len);
-                dst.put_u16(header.id);
-                dst.put_u16(header.msg_id);
-                dst.put_u32(header.len);
-                dst.put_u32(msg.recid);
-                dst.put_u8(msg.atype);
-                dst.put_u8(msg.rtlen);
-                dst.put_u16(msg.rnlen);
-                dst.put_slice(msg.rtype.as_bytes());
-                dst.put_slice(msg.rname.as_bytes());
-                Ok(())
-            },
-            Message::DelRecord(_) => todo!(),
-            Message::AddInfo(msg) => {
-                let len = (size_of::<u32>() + size_of::<u8>() + size_of::<u8>() + size_of::<u16>() + msg.key.len() + msg.value.len()) as u32;
-                let header = MessageHeader::new(MessageID::AddInfo.into(), len);
-                dst.put_u16(header.id);
-                dst.put_u16(header.msg_id);
-                dst.put_u32(header.len);
-                dst.put_u32(msg.recid);
-                dst.put_u8(msg.keylen);
-                dst.put_u8(0); // Padding
-                dst.put_u16(msg.valen);
-                dst.put_slice(msg.key.as_bytes());
-                dst.put_slice(msg.value.as_bytes());
-                Ok(())
-            },
-            Message::UploadDone(_) => {
-                let header = MessageHeader::new(MessageID::UploadDone.into(), size_of::<u32>() as u32);
-                dst.put(header.as_bytes());
-                dst.put_u32(0);
-                Ok(())
-            },
-            Message::Ping(_) => unimplemented!("Recceiver related messages are not implemented yet."),
-            Message::ServerGreet(_) => unimplemented!("Recceiver related messages are not implemented yet.")
-        }
+#[derive(Debug, Clone)]
+pub struct MessageCodec {
+    unknown_policy: UnknownPolicy,
+    utf8_mode: Utf8Mode,
+    max_frame_len: usize,
+}
+
+impl Default for MessageCodec {
+    fn default() -> Self {
+        MessageCodec { unknown_policy: UnknownPolicy::default(), utf8_mode: Utf8Mode::default(), max_frame_len: DEFAULT_MAX_FRAME_LEN }
     }
 }
 
-impl Decoder for MessageCodec {
-    type Item = Message;
-    type Error = io::Error;
+impl MessageCodec {
+    pub fn new() -> MessageCodec {
+        MessageCodec::default()
+    }
 
-    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        if src.len() < 8 {
-            // Not enough data to read header
-            return Ok(None);
-        }
+    /// Create a codec that rejects frames longer than `max_frame_len` bytes, header included
+    pub fn with_max_frame_len(max_frame_len: usize) -> MessageCodec {
+        MessageCodec { max_frame_len, ..MessageCodec::default() }
+    }
+
+    /// Return the maximum frame length, header included
+    pub fn max_frame_len(&self) -> usize {
+        self.max_frame_len
+    }
+
+    /// Return how frames with an unknown message ID are decoded
+    pub fn unknown_policy(&self) -> UnknownPolicy {
+        self.unknown_policy
+    }
+
+    /// Set how frames with an unknown message ID are decoded
+    pub fn set_unknown_policy(&mut self, policy: UnknownPolicy) {
+        self.unknown_policy = policy;
+    }
+
+    /// Return how strings that are not valid UTF-8 are decoded
+    pub fn utf8_mode(&self) -> Utf8Mode {
+        self.utf8_mode
+    }
+
+    /// Set how strings that are not valid UTF-8 are decoded
+    pub fn set_utf8_mode(&mut self, mode: Utf8Mode) {
+        self.utf8_mode = mode;
+    }
+}
+
+impl Message {
+    /// Number of bytes [`Message::encode_to`] writes, header included
+    pub fn encoded_len(&self) -> usize {
+        HEADER_LEN + self.body_len()
+    }
+
+    /// Write the message frame to `dst`, nothing is written if the message is invalid
+    pub fn encode_to(&self, dst: &mut impl BufMut) -> Result<(), WireError> {
+        self.validate()?;
+        self.encode_fixed(dst);
+        self.for_each_text(|field| dst.put_slice(field));
+        Ok(())
+    }
+
+    /// Write the header and fixed size fields, everything but the text fields
+    pub(crate) fn encode_fixed(&self, dst: &mut impl BufMut) {
+        let header = MessageHeader::new(self.msg_id(), self.body_len() as u32);
+        header.encode_to(dst);
+        self.encode_fixed_body(dst);
+    }
+}
+
+impl MessageCodec {
+    /// Check the header at the start of `src` and return the length of its frame, header included.
+    /// Returns `None` if fewer than [`HEADER_LEN`] bytes are available.
+    pub fn frame_len(&self, src: &[u8]) -> Result<Option<usize>, WireError> {
+        let header = match MessageHeader::peek(src) {
+            Some(header) => header,
+            None => return Ok(None),
+        };
 
-        // Read header
-        let id = src.get_u16();
-        let msg_id = src.get_u16();
-        let len = src.get_u32() as usize;
-        
         // Checking if the ID is 'RC'
-        if id != MSG_MAGIC_ID {
-            return Ok(None);
+        if header.id != MSG_MAGIC_ID {
+            return Err(WireError::BadMagic(header.id));
+        }
+
+        // Refuse to buffer oversize frames from a corrupt or malicious header
+        let frame_len = HEADER_LEN + header.len as usize;
+        if frame_len > self.max_frame_len {
+            return Err(WireError::FrameTooLarge { len: frame_len, max: self.max_frame_len });
         }
+        Ok(Some(frame_len))
+    }
 
-        if src.len() < len {
-            // Not enough data to read the body
-            return Ok(None);
+    /// Decode one complete frame, header included.
+    /// Returns `None` if the frame carries an unknown message that the policy skips.
+    pub fn decode_frame(&self, mut frame: Bytes) -> Result<Option<Message>, WireError> {
+        let len = match self.frame_len(&frame)? {
+            Some(len) => len,
+            None => return Err(WireError::Truncated { needed: HEADER_LEN, available: frame.len() }),
+        };
+        if frame.len() != len {
+            return Err(WireError::Truncated { needed: len, available: frame.len() });
         }
+        let header = MessageHeader::peek(&frame).expect("frame_len checked the header");
+        frame.advance(HEADER_LEN);
+        let mut body = frame;
 
-        // Match based on `msg_id` and parse accordingly
-        match msg_id.into() {
-            MessageID::ServerGreet => {
-                let _placeholder = src.get_u8();
-                Ok(Some(Message::ServerGreet(ServerGreet)))
-            }
-            MessageID::Ping => {
-                let nonce = src.get_u32();
-                Ok(Some(Message::Ping(Ping { nonce })))
+        let msg_id = match MessageID::try_from(header.msg_id) {
+            Ok(msg_id) => msg_id,
+            Err(err) => return match self.unknown_policy {
+                UnknownPolicy::Preserve => Ok(Some(Message::Unknown { msg_id: header.msg_id, body })),
+                UnknownPolicy::Skip => Ok(None),
+                UnknownPolicy::Reject => Err(err),
             },
-            MessageID::ClientGreet => unimplemented!("Recceiver related messages are not implemented yet."),
-            MessageID::Pong => unimplemented!("Recceiver related messages are not implemented yet."),
-            MessageID::AddRecord => unimplemented!("Recceiver related messages are not implemented yet."),
-            MessageID::DelRecord => unimplemented!("Recceiver related messages are not implemented yet."),
-            MessageID::UploadDone => unimplemented!("Recceiver related messages are not implemented yet."),
-            MessageID::AddInfo => unimplemented!("Recceiver related messages are not implemented yet."),
+        };
+        let msg = Message::decode_body(msg_id, &mut body, self.utf8_mode)?;
+
+        // The body must be consumed exactly
+        if body.has_remaining() {
+            return Err(WireError::BadLength { msg_id: header.msg_id, len: header.len });
+        }
+        Ok(Some(msg))
+    }
+
+    /// Decode the next message from a stream buffer.
+    /// Nothing is consumed until a whole frame is buffered, so `src` can be filled a byte at a time.
+    pub fn decode_message(&self, src: &mut BytesMut) -> Result<Option<Message>, WireError> {
+        loop {
+            let frame_len = match self.frame_len(src)? {
+                Some(frame_len) => frame_len,
+                None => return Ok(None),
+            };
+            if src.len() < frame_len {
+                // Not enough data to read the body
+                src.reserve(frame_len - src.len());
+                return Ok(None);
+            }
+            let frame = src.split_to(frame_len).freeze();
+            if let Some(msg) = self.decode_frame(frame)? {
+                return Ok(Some(msg));
+            }
         }
     }
 }
+
+/// Check that at least `needed` bytes are left in the message body
+pub(crate) fn ensure_remaining(body: &Bytes, needed: usize) -> Result<(), WireError> {
+    if body.remaining() < needed {
+        return Err(WireError::Truncated { needed, available: body.remaining() });
+    }
+    Ok(())
+}
+
+/// Take a `len` bytes long string from the buffer, it is only copied when lossy decoding has to replace invalid UTF-8
+pub(crate) fn get_bytes(src: &mut Bytes, len: usize, mode: Utf8Mode) -> Result<Bytes, WireError> {
+    ensure_remaining(src, len)?;
+    let bytes = src.split_to(len);
+    match mode {
+        Utf8Mode::Strict => {
+            std::str::from_utf8(&bytes)?;
+            Ok(bytes)
+        },
+        Utf8Mode::Lossy => match String::from_utf8_lossy(&bytes) {
+            Cow::Borrowed(_) => Ok(bytes),
+            Cow::Owned(string) => Ok(Bytes::from(string)),
+        },
+        Utf8Mode::Raw => Ok(bytes),
+    }
+}
@@ -0,0 +1,217 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+//! Messages split by the peer that sends them, so a RecCaster cannot encode a `Ping`
+//! and a RecCeiver cannot decode a `ServerGreet`. [`Message`] and [`MessageCodec`] stay
+//! for tools that see both directions, like the dissector.
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+use crate::{AddInfo, AddRecord, ClientGreet, DelRecord, Message, MessageCodec, Ping, Pong, ServerGreet, UnknownPolicy, UploadDone, Utf8Mode, WireError};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Messages sent by a RecCaster to a RecCeiver
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ClientMessage {
+    ClientGreet(ClientGreet),
+    Pong(Pong),
+    AddRecord(AddRecord),
+    DelRecord(DelRecord),
+    UploadDone(UploadDone),
+    AddInfo(AddInfo),
+    /// Message with an ID this crate does not know about, kept as its raw body
+    Unknown {
+        msg_id: u16,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_text"))]
+        body: Bytes,
+    },
+}
+
+/// Messages sent by a RecCeiver to a RecCaster
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ServerMessage {
+    ServerGreet(ServerGreet),
+    Ping(Ping),
+    /// Message with an ID this crate does not know about, kept as its raw body
+    Unknown {
+        msg_id: u16,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_text"))]
+        body: Bytes,
+    },
+}
+
+impl From<ClientMessage> for Message {
+    fn from(msg: ClientMessage) -> Message {
+        match msg {
+            ClientMessage::ClientGreet(msg) => Message::ClientGreet(msg),
+            ClientMessage::Pong(msg) => Message::Pong(msg),
+            ClientMessage::AddRecord(msg) => Message::AddRecord(msg),
+            ClientMessage::DelRecord(msg) => Message::DelRecord(msg),
+            ClientMessage::UploadDone(msg) => Message::UploadDone(msg),
+            ClientMessage::AddInfo(msg) => Message::AddInfo(msg),
+            ClientMessage::Unknown { msg_id, body } => Message::Unknown { msg_id, body },
+        }
+    }
+}
+
+impl From<ServerMessage> for Message {
+    fn from(msg: ServerMessage) -> Message {
+        match msg {
+            ServerMessage::ServerGreet(msg) => Message::ServerGreet(msg),
+            ServerMessage::Ping(msg) => Message::Ping(msg),
+            ServerMessage::Unknown { msg_id, body } => Message::Unknown { msg_id, body },
+        }
+    }
+}
+
+impl TryFrom<Message> for ClientMessage {
+    type Error = WireError;
+
+    /// Fails with [`WireError::WrongDirection`] for messages only a RecCeiver sends
+    fn try_from(msg: Message) -> Result<Self, Self::Error> {
+        match msg {
+            Message::ClientGreet(msg) => Ok(ClientMessage::ClientGreet(msg)),
+            Message::Pong(msg) => Ok(ClientMessage::Pong(msg)),
+            Message::AddRecord(msg) => Ok(ClientMessage::AddRecord(msg)),
+            Message::DelRecord(msg) => Ok(ClientMessage::DelRecord(msg)),
+            Message::UploadDone(msg) => Ok(ClientMessage::UploadDone(msg)),
+            Message::AddInfo(msg) => Ok(ClientMessage::AddInfo(msg)),
+            Message::Unknown { msg_id, body } => Ok(ClientMessage::Unknown { msg_id, body }),
+            msg @ (Message::ServerGreet(_) | Message::Ping(_)) => Err(WireError::WrongDirection(msg.msg_id())),
+        }
+    }
+}
+
+impl TryFrom<Message> for ServerMessage {
+    type Error = WireError;
+
+    /// Fails with [`WireError::WrongDirection`] for messages only a RecCaster sends
+    fn try_from(msg: Message) -> Result<Self, Self::Error> {
+        match msg {
+            Message::ServerGreet(msg) => Ok(ServerMessage::ServerGreet(msg)),
+            Message::Ping(msg) => Ok(ServerMessage::Ping(msg)),
+            Message::Unknown { msg_id, body } => Ok(ServerMessage::Unknown { msg_id, body }),
+            msg => Err(WireError::WrongDirection(msg.msg_id())),
+        }
+    }
+}
+
+impl ClientMessage {
+    /// Number of bytes [`ClientMessage::encode_to`] writes, header included
+    pub fn encoded_len(&self) -> usize {
+        Message::from(self.clone()).encoded_len()
+    }
+
+    /// Write the message frame to `dst`, nothing is written if the message is invalid
+    pub fn encode_to(&self, dst: &mut impl BufMut) -> Result<(), WireError> {
+        Message::from(self.clone()).encode_to(dst)
+    }
+}
+
+impl ServerMessage {
+    /// Number of bytes [`ServerMessage::encode_to`] writes, header included
+    pub fn encoded_len(&self) -> usize {
+        Message::from(self.clone()).encoded_len()
+    }
+
+    /// Write the message frame to `dst`
+    pub fn encode_to(&self, dst: &mut impl BufMut) -> Result<(), WireError> {
+        Message::from(self.clone()).encode_to(dst)
+    }
+}
+
+/// RecCaster side codec, encodes [`ClientMessage`] and decodes [`ServerMessage`].
+/// Decoding a message that only a RecCaster sends fails with [`WireError::WrongDirection`].
+#[derive(Debug, Clone, Default)]
+pub struct ClientCodec {
+    pub(crate) codec: MessageCodec,
+}
+
+impl ClientCodec {
+    pub fn new() -> ClientCodec {
+        ClientCodec::default()
+    }
+
+    /// Create a codec that rejects frames longer than `max_frame_len` bytes, header included
+    pub fn with_max_frame_len(max_frame_len: usize) -> ClientCodec {
+        ClientCodec { codec: MessageCodec::with_max_frame_len(max_frame_len) }
+    }
+
+    /// Return the codec holding the decoding settings
+    pub fn codec(&self) -> &MessageCodec {
+        &self.codec
+    }
+
+    /// Set how frames with an unknown message ID are decoded
+    pub fn set_unknown_policy(&mut self, policy: UnknownPolicy) {
+        self.codec.set_unknown_policy(policy);
+    }
+
+    /// Set how strings that are not valid UTF-8 are decoded
+    pub fn set_utf8_mode(&mut self, mode: Utf8Mode) {
+        self.codec.set_utf8_mode(mode);
+    }
+
+    /// Decode the next message from a stream buffer, see [`MessageCodec::decode_message`]
+    pub fn decode_message(&self, src: &mut BytesMut) -> Result<Option<ServerMessage>, WireError> {
+        self.codec.decode_message(src)?.map(ServerMessage::try_from).transpose()
+    }
+}
+
+impl From<MessageCodec> for ClientCodec {
+    fn from(codec: MessageCodec) -> ClientCodec {
+        ClientCodec { codec }
+    }
+}
+
+/// RecCeiver side codec, encodes [`ServerMessage`] and decodes [`ClientMessage`].
+/// Decoding a message that only a RecCeiver sends fails with [`WireError::WrongDirection`].
+#[derive(Debug, Clone, Default)]
+pub struct ServerCodec {
+    pub(crate) codec: MessageCodec,
+}
+
+impl ServerCodec {
+    pub fn new() -> ServerCodec {
+        ServerCodec::default()
+    }
+
+    /// Create a codec that rejects frames longer than `max_frame_len` bytes, header included
+    pub fn with_max_frame_len(max_frame_len: usize) -> ServerCodec {
+        ServerCodec { codec: MessageCodec::with_max_frame_len(max_frame_len) }
+    }
+
+    /// Return the codec holding the decoding settings
+    pub fn codec(&self) -> &MessageCodec {
+        &self.codec
+    }
+
+    /// Set how frames with an unknown message ID are decoded
+    pub fn set_unknown_policy(&mut self, policy: UnknownPolicy) {
+        self.codec.set_unknown_policy(policy);
+    }
+
+    /// Set how strings that are not valid UTF-8 are decoded
+    pub fn set_utf8_mode(&mut self, mode: Utf8Mode) {
+        self.codec.set_utf8_mode(mode);
+    }
+
+    /// Decode the next message from a stream buffer, see [`MessageCodec::decode_message`]
+    pub fn decode_message(&self, src: &mut BytesMut) -> Result<Option<ClientMessage>, WireError> {
+        self.codec.decode_message(src)?.map(ClientMessage::try_from).transpose()
+    }
+}
+
+impl From<MessageCodec> for ServerCodec {
+    fn from(codec: MessageCodec) -> ServerCodec {
+        ServerCodec { codec }
+    }
+}
@@ -0,0 +1,86 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+use std::{fmt, io, str::Utf8Error};
+
+use crate::{session::Phase, Direction};
+
+/// Errors raised while encoding or decoding recsync messages
+#[derive(Debug)]
+pub enum WireError {
+    /// Header does not start with the "RC" magic number
+    BadMagic(u16),
+    /// Header carries a message ID this crate does not know about
+    UnknownMessageId(u16),
+    /// Unknown message carries the ID of a message this crate knows, it would decode as that message
+    KnownMessageId(u16),
+    /// Message body is shorter than its fields require
+    Truncated { needed: usize, available: usize },
+    /// Header length does not match the message body
+    BadLength { msg_id: u16, len: u32 },
+    /// Header announces a frame longer than the codec accepts
+    FrameTooLarge { len: usize, max: usize },
+    /// String field is too long for its length prefix
+    FieldTooLong { field: &'static str, len: usize, max: usize },
+    /// Announcement uses a format version this crate does not understand
+    UnsupportedVersion(u8),
+    /// String field is not valid UTF-8
+    InvalidUtf8(Utf8Error),
+    /// Message is only sent in the other direction
+    WrongDirection(u16),
+    /// Message is not allowed at this point of the session
+    UnexpectedMessage { phase: Phase, direction: Direction, msg_id: u16 },
+    /// Message refers to a record that was not added in the session
+    UnknownRecid(u32),
+    /// Transcript line cannot be parsed
+    Syntax { line: usize, reason: String },
+    /// Underlying I/O error
+    Io(io::Error),
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WireError::BadMagic(id) => write!(f, "bad magic id {:#06x}, expected {:#06x}", id, crate::MSG_MAGIC_ID),
+            WireError::UnknownMessageId(msg_id) => write!(f, "unknown message id {:#06x}", msg_id),
+            WireError::KnownMessageId(msg_id) => write!(f, "unknown message uses the known message id {:#06x}", msg_id),
+            WireError::Truncated { needed, available } => write!(f, "truncated message body, needed {} bytes but only {} available", needed, available),
+            WireError::BadLength { msg_id, len } => write!(f, "bad length {} for message id {:#06x}", len, msg_id),
+            WireError::FrameTooLarge { len, max } => write!(f, "frame of {} bytes exceeds the {} bytes limit", len, max),
+            WireError::FieldTooLong { field, len, max } => write!(f, "{} is {} bytes long, at most {} bytes fit in the message", field, len, max),
+            WireError::UnsupportedVersion(version) => write!(f, "unsupported announcement version {}", version),
+            WireError::InvalidUtf8(err) => write!(f, "invalid UTF-8 string: {}", err),
+            WireError::WrongDirection(msg_id) => write!(f, "message id {:#06x} is sent in the other direction", msg_id),
+            WireError::UnexpectedMessage { phase, direction, msg_id } => write!(f, "unexpected {} message id {:#06x}, session is {}", direction, msg_id, phase),
+            WireError::UnknownRecid(recid) => write!(f, "record id {} was not added in this session", recid),
+            WireError::Syntax { line, reason } => write!(f, "line {}: {}", line, reason),
+            WireError::Io(err) => write!(f, "I/O error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for WireError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WireError::InvalidUtf8(err) => Some(err),
+            WireError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for WireError {
+    fn from(err: io::Error) -> Self {
+        WireError::Io(err)
+    }
+}
+
+impl From<Utf8Error> for WireError {
+    fn from(err: Utf8Error) -> Self {
+        WireError::InvalidUtf8(err)
+    }
+}
@@ -6,10 +6,16 @@
 // See the LICENSE file for details.
 
 use bytes::{BufMut, BytesMut};
-use std::mem::size_of;
 use crate::MSG_MAGIC_ID;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Size of the message header on the wire
+pub const HEADER_LEN: usize = 8;
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct MessageHeader {
     pub id: u16,
     pub msg_id: u16,
@@ -21,12 +27,30 @@ impl MessageHeader {
         MessageHeader { id: MSG_MAGIC_ID, msg_id, len }
     }
 
+    /// Read a header from the start of `src` without consuming it.
+    /// Returns `None` if fewer than [`HEADER_LEN`] bytes are available.
+    pub fn peek(src: &[u8]) -> Option<MessageHeader> {
+        if src.len() < HEADER_LEN {
+            return None;
+        }
+        Some(MessageHeader {
+            id: u16::from_be_bytes([src[0], src[1]]),
+            msg_id: u16::from_be_bytes([src[2], src[3]]),
+            len: u32::from_be_bytes([src[4], src[5], src[6], src[7]]),
+        })
+    }
+
+    /// Write the header to `dst`
+    pub fn encode_to(&self, dst: &mut impl BufMut) {
+        dst.put_u16(self.id);
+        dst.put_u16(self.msg_id);
+        dst.put_u32(self.len);
+    }
+
     /// Return Header as BytesMut
     pub fn as_bytes(&self) -> BytesMut {
-        let mut buf = BytesMut::with_capacity(size_of::<MessageHeader>());
-        buf.put_u16(self.id);
-        buf.put_u16(self.msg_id);
-        buf.put_u32(self.len);
+        let mut buf = BytesMut::with_capacity(HEADER_LEN);
+        self.encode_to(&mut buf);
         buf
     }
 }
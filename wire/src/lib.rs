@@ -5,10 +5,38 @@
 // You must comply with both licenses to use, modify, or distribute this software.
 // See the LICENSE file for details.
 
+//! Messages of the recsync protocol between RecCasters and RecCeivers, with their wire encoding.
+//!
+//! Features:
+//! * `tokio`: `tokio_util` `Encoder`/`Decoder` implementations
+//! * `serde`: `Serialize`/`Deserialize` for the message types
+//! * `arbitrary`: `Arbitrary` impls and proptest strategies in `wire::strategy`.
+//!   The crate builds with Rust 1.70, this feature needs Rust 1.88 or later because proptest does.
+
 mod header;
 mod codec;
 mod types;
+mod error;
+mod blocking;
+mod upload;
+mod transcript;
+mod directional;
+pub mod session;
+pub mod schema;
+#[cfg(feature = "serde")]
+mod serde_text;
+#[cfg(feature = "tokio")]
+mod tokio_codec;
+#[cfg(feature = "arbitrary")]
+pub mod strategy;
 
 pub use types::*;
 pub use codec::*;
 pub use header::*;
+pub use error::*;
+pub use blocking::*;
+pub use upload::*;
+pub use transcript::*;
+pub use directional::*;
+#[cfg(feature = "tokio")]
+pub use tokio_codec::*;
@@ -0,0 +1,326 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+//! Wire layout of every message body, described once in the `schema!` table at the bottom.
+//!
+//! Each field is one of:
+//! * `value name: u32`, a struct field sent as a big endian integer
+//! * `pad name: u16`, bytes sent as zeros and ignored when decoding
+//! * `len name: u8`, the length prefix of the `text` field with the same name, which also bounds its length
+//! * `text name: Bytes`, string bytes, after all the fixed size fields
+//!
+//! Encoding, decoding, lengths and validation of [`Message`] are generated from the table,
+//! and [`LAYOUTS`] exposes it so tests and tools can check frames against it.
+
+use bytes::{Buf, BufMut, Bytes};
+
+use crate::{codec::{ensure_remaining, get_bytes, DEFAULT_MAX_FRAME_LEN}, header::HEADER_LEN, types::check_len, AddInfo, AddRecord, ClientGreet, DelRecord, Message, MessageID, Ping, Pong, ServerGreet, UploadDone, Utf8Mode, WireError};
+
+/// How a field of a message body is sent
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FieldKind {
+    /// Integer of the given size in bytes
+    Value(usize),
+    /// Zeroed bytes
+    Padding(usize),
+    /// Length prefix of the given size in bytes for the text field with the same name
+    Length(usize),
+    /// String bytes, as long as their length prefix says
+    Text,
+}
+
+/// One field of a message body, in wire order
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FieldLayout {
+    pub name: &'static str,
+    pub kind: FieldKind,
+}
+
+/// Body layout of one message type
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MessageLayout {
+    pub name: &'static str,
+    pub msg_id: u16,
+    pub fields: &'static [FieldLayout],
+}
+
+impl MessageLayout {
+    /// Size of the fixed size fields, the whole body for messages without text
+    pub const fn fixed_len(&self) -> usize {
+        let mut len = 0;
+        let mut i = 0;
+        while i < self.fields.len() {
+            len += match self.fields[i].kind {
+                FieldKind::Value(size) | FieldKind::Padding(size) | FieldKind::Length(size) => size,
+                FieldKind::Text => 0,
+            };
+            i += 1;
+        }
+        len
+    }
+}
+
+/// Longest body of any known message, with every text field as long as its length prefix allows
+pub const fn max_body_len() -> usize {
+    let mut max = 0;
+    let mut i = 0;
+    while i < LAYOUTS.len() {
+        let layout = &LAYOUTS[i];
+        let mut len = layout.fixed_len();
+        let mut j = 0;
+        while j < layout.fields.len() {
+            if let FieldKind::Length(size) = layout.fields[j].kind {
+                len += (1 << (8 * size)) - 1;
+            }
+            j += 1;
+        }
+        if len > max {
+            max = len;
+        }
+        i += 1;
+    }
+    max
+}
+
+/// Return the layout of a known message ID
+pub fn layout(msg_id: u16) -> Option<&'static MessageLayout> {
+    LAYOUTS.iter().find(|layout| layout.msg_id == msg_id)
+}
+
+/// Big endian integers used on the wire
+pub(crate) trait WireInt: Copy {
+    const LEN: usize;
+    const MAX: usize;
+    fn put(self, dst: &mut impl BufMut);
+    fn get(src: &mut Bytes) -> Self;
+    fn from_len(len: usize) -> Self;
+    fn to_len(self) -> usize;
+}
+
+macro_rules! wire_int {
+    ($($ty:ident: $put:ident, $get:ident;)*) => {
+        $(
+            impl WireInt for $ty {
+                const LEN: usize = std::mem::size_of::<$ty>();
+                const MAX: usize = $ty::MAX as usize;
+
+                fn put(self, dst: &mut impl BufMut) {
+                    dst.$put(self);
+                }
+
+                fn get(src: &mut Bytes) -> Self {
+                    src.$get()
+                }
+
+                fn from_len(len: usize) -> Self {
+                    len as $ty
+                }
+
+                fn to_len(self) -> usize {
+                    self as usize
+                }
+            }
+        )*
+    };
+}
+
+wire_int! {
+    u8: put_u8, get_u8;
+    u16: put_u16, get_u16;
+    u32: put_u32, get_u32;
+}
+
+/// Encoding and decoding of a message body, implemented by `schema!`
+pub(crate) trait Body: Sized {
+    const LAYOUT: MessageLayout;
+    const FIXED_LEN: usize = Self::LAYOUT.fixed_len();
+
+    fn body_len(&self) -> usize;
+    fn validate(&self) -> Result<(), WireError>;
+    fn encode_fixed(&self, dst: &mut impl BufMut);
+    fn for_each_text<'a>(&'a self, f: &mut dyn FnMut(&'a Bytes));
+    fn decode(body: &mut Bytes, mode: Utf8Mode) -> Result<Self, WireError>;
+}
+
+/// Code for one field, selected by its kind
+macro_rules! field {
+    (@layout value $name:ident $ty:ident) => { FieldLayout { name: stringify!($name), kind: FieldKind::Value(<$ty as WireInt>::LEN) } };
+    (@layout pad $name:ident $ty:ident) => { FieldLayout { name: stringify!($name), kind: FieldKind::Padding(<$ty as WireInt>::LEN) } };
+    (@layout len $name:ident $ty:ident) => { FieldLayout { name: stringify!($name), kind: FieldKind::Length(<$ty as WireInt>::LEN) } };
+    (@layout text $name:ident $ty:ident) => { FieldLayout { name: stringify!($name), kind: FieldKind::Text } };
+
+    (@text_len $self:ident text $name:ident) => { $self.$name.len() };
+    (@text_len $self:ident $kind:ident $name:ident) => { 0 };
+
+    (@validate $self:ident len $name:ident $ty:ident) => { check_len(stringify!($name), &$self.$name, <$ty as WireInt>::MAX)? };
+    (@validate $self:ident $kind:ident $name:ident $ty:ident) => {};
+
+    (@encode $self:ident $dst:ident value $name:ident $ty:ident) => { <$ty as WireInt>::put($self.$name, $dst) };
+    (@encode $self:ident $dst:ident pad $name:ident $ty:ident) => { <$ty as WireInt>::put(0, $dst) };
+    (@encode $self:ident $dst:ident len $name:ident $ty:ident) => { <$ty as WireInt>::put(<$ty as WireInt>::from_len($self.$name.len()), $dst) };
+    (@encode $self:ident $dst:ident text $name:ident $ty:ident) => {};
+
+    (@text $self:ident $f:ident text $name:ident) => { $f(&$self.$name) };
+    (@text $self:ident $f:ident $kind:ident $name:ident) => {};
+
+    // Length prefixes are bound to the name of their text field, which then shadows them
+    (@decode $body:ident $mode:ident value $name:ident $ty:ident) => { let $name = <$ty as WireInt>::get($body); };
+    (@decode $body:ident $mode:ident pad $name:ident $ty:ident) => { let _ = <$ty as WireInt>::get($body); };
+    (@decode $body:ident $mode:ident len $name:ident $ty:ident) => { let $name = <$ty as WireInt>::get($body).to_len(); };
+    (@decode $body:ident $mode:ident text $name:ident $ty:ident) => { let $name = get_bytes($body, $name, $mode)?; };
+
+    // Struct literal from the value and text fields
+    (@build $msg:ident [$($field:ident)*]) => { $msg { $($field),* } };
+    (@build $msg:ident [$($field:ident)*] value $name:ident $($rest:tt)*) => { field!(@build $msg [$($field)* $name] $($rest)*) };
+    (@build $msg:ident [$($field:ident)*] text $name:ident $($rest:tt)*) => { field!(@build $msg [$($field)* $name] $($rest)*) };
+    (@build $msg:ident [$($field:ident)*] $kind:ident $name:ident $($rest:tt)*) => { field!(@build $msg [$($field)*] $($rest)*) };
+}
+
+/// Generate the [`Body`] impls, the [`Message`] dispatch and [`LAYOUTS`] from the message table.
+/// Message names are the names of the struct, the [`Message`] variant and the [`MessageID`].
+macro_rules! schema {
+    ($($msg:ident { $($kind:ident $name:ident: $ty:ident),* $(,)? })*) => {
+        $(
+            impl Body for $msg {
+                const LAYOUT: MessageLayout = MessageLayout {
+                    name: stringify!($msg),
+                    msg_id: MessageID::$msg as u16,
+                    fields: &[$(field!(@layout $kind $name $ty)),*],
+                };
+
+                fn body_len(&self) -> usize {
+                    Self::FIXED_LEN $(+ field!(@text_len self $kind $name))*
+                }
+
+                fn validate(&self) -> Result<(), WireError> {
+                    $(field!(@validate self $kind $name $ty);)*
+                    Ok(())
+                }
+
+                #[allow(unused_variables)]
+                fn encode_fixed(&self, dst: &mut impl BufMut) {
+                    $(field!(@encode self dst $kind $name $ty);)*
+                }
+
+                #[allow(unused_variables)]
+                fn for_each_text<'a>(&'a self, f: &mut dyn FnMut(&'a Bytes)) {
+                    $(field!(@text self f $kind $name);)*
+                }
+
+                #[allow(unused_variables)]
+                fn decode(body: &mut Bytes, mode: Utf8Mode) -> Result<Self, WireError> {
+                    ensure_remaining(body, Self::FIXED_LEN)?;
+                    $(field!(@decode body mode $kind $name $ty);)*
+                    Ok(field!(@build $msg [] $($kind $name)*))
+                }
+            }
+        )*
+
+        /// Body layout of every known message
+        pub const LAYOUTS: &[MessageLayout] = &[$(<$msg as Body>::LAYOUT),*];
+
+        impl Message {
+            /// Size of the header and fixed size fields
+            pub(crate) fn fixed_len(&self) -> usize {
+                HEADER_LEN + match self {
+                    $(Message::$msg(_) => <$msg as Body>::FIXED_LEN,)*
+                    Message::Unknown { .. } => 0,
+                }
+            }
+
+            pub(crate) fn body_len(&self) -> usize {
+                match self {
+                    $(Message::$msg(msg) => msg.body_len(),)*
+                    Message::Unknown { body, .. } => body.len(),
+                }
+            }
+
+            /// Check the string field lengths fit in their length fields, and that an unknown message
+            /// neither uses a known ID nor is too large to be decoded
+            pub(crate) fn validate(&self) -> Result<(), WireError> {
+                match self {
+                    $(Message::$msg(msg) => Body::validate(msg),)*
+                    Message::Unknown { msg_id, .. } if MessageID::try_from(*msg_id).is_ok() => Err(WireError::KnownMessageId(*msg_id)),
+                    Message::Unknown { body, .. } if HEADER_LEN + body.len() > DEFAULT_MAX_FRAME_LEN => {
+                        Err(WireError::FrameTooLarge { len: HEADER_LEN + body.len(), max: DEFAULT_MAX_FRAME_LEN })
+                    },
+                    Message::Unknown { .. } => Ok(()),
+                }
+            }
+
+            /// Write the fixed size fields of the body
+            pub(crate) fn encode_fixed_body(&self, dst: &mut impl BufMut) {
+                match self {
+                    $(Message::$msg(msg) => msg.encode_fixed(dst),)*
+                    Message::Unknown { .. } => {},
+                }
+            }
+
+            /// Call `f` with the variable length fields that follow the fixed size fields, in order
+            pub(crate) fn for_each_text<'a>(&'a self, mut f: impl FnMut(&'a Bytes)) {
+                match self {
+                    $(Message::$msg(msg) => msg.for_each_text(&mut f),)*
+                    Message::Unknown { body, .. } => f(body),
+                }
+            }
+
+            /// Message ID sent in the header
+            pub fn msg_id(&self) -> u16 {
+                match self {
+                    $(Message::$msg(_) => MessageID::$msg as u16,)*
+                    Message::Unknown { msg_id, .. } => *msg_id,
+                }
+            }
+
+            pub(crate) fn decode_body(msg_id: MessageID, body: &mut Bytes, mode: Utf8Mode) -> Result<Message, WireError> {
+                match msg_id {
+                    $(MessageID::$msg => Ok(Message::$msg(<$msg as Body>::decode(body, mode)?)),)*
+                }
+            }
+        }
+    };
+}
+
+schema! {
+    ServerGreet {
+        value server_type: u8,
+    }
+    ClientGreet {
+        value version: u8,
+        value client_type: u8,
+        pad reserved: u16,
+        value serv_key: u32,
+    }
+    Ping {
+        value nonce: u32,
+    }
+    Pong {
+        value nonce: u32,
+    }
+    AddRecord {
+        value recid: u32,
+        value atype: u8,
+        len rtype: u8,
+        len rname: u16,
+        text rtype: Bytes,
+        text rname: Bytes,
+    }
+    DelRecord {
+        value recid: u32,
+    }
+    UploadDone {
+        pad reserved: u32,
+    }
+    AddInfo {
+        value recid: u32,
+        len key: u8,
+        pad reserved: u8,
+        len value: u16,
+        text key: Bytes,
+        text value: Bytes,
+    }
+}
@@ -0,0 +1,68 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+//! Serialize `Bytes` string fields as text when they hold valid UTF-8, and as raw bytes otherwise,
+//! so JSON dumps stay readable while names decoded in raw mode survive a round trip.
+//! Formats that are not human readable, like bincode, cannot tell the two apart when deserializing,
+//! so they always get the raw bytes.
+
+use std::fmt;
+
+use bytes::Bytes;
+use serde::{de::{self, SeqAccess, Visitor}, Deserializer, Serializer};
+
+pub fn serialize<S: Serializer>(value: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+    if !serializer.is_human_readable() {
+        return serializer.serialize_bytes(value);
+    }
+    match std::str::from_utf8(value) {
+        Ok(text) => serializer.serialize_str(text),
+        Err(_) => serializer.serialize_bytes(value),
+    }
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+    if deserializer.is_human_readable() {
+        deserializer.deserialize_any(TextVisitor)
+    } else {
+        deserializer.deserialize_byte_buf(TextVisitor)
+    }
+}
+
+struct TextVisitor;
+
+impl<'de> Visitor<'de> for TextVisitor {
+    type Value = Bytes;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a string or a byte array")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Bytes, E> {
+        Ok(Bytes::copy_from_slice(value.as_bytes()))
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> Result<Bytes, E> {
+        Ok(Bytes::from(value))
+    }
+
+    fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<Bytes, E> {
+        Ok(Bytes::copy_from_slice(value))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, value: Vec<u8>) -> Result<Bytes, E> {
+        Ok(Bytes::from(value))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Bytes, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element::<u8>()? {
+            bytes.push(byte);
+        }
+        Ok(Bytes::from(bytes))
+    }
+}
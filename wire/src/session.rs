@@ -0,0 +1,251 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+//! Legal message order of a recsync session, shared by casters, servers and analysis tools.
+//!
+//! ```text
+//! S->C ServerGreet
+//! C->S ClientGreet
+//! C->S AddRecord / AddInfo / DelRecord ...
+//! C->S UploadDone
+//! C->S DelRecord ...
+//! ```
+//!
+//! `Ping` (S->C) and `Pong` (C->S) are allowed at any time, and so are unknown messages.
+//! Records can still be deleted after the upload, e.g. by a RecCaster shutting down.
+//! Aliases, info tags and deletions must refer to a record added earlier in the session.
+//!
+//! [`SessionChecker`] follows a session one message at a time, for code that decodes messages at run time.
+//! [`Session`] encodes the same rules in its type, so code that builds a session cannot get the order wrong.
+
+use std::{collections::HashSet, fmt, marker::PhantomData};
+
+use crate::{AddInfo, AddRecord, AddRecordType, ClientGreet, ClientMessage, DelRecord, Direction, Message, Ping, Pong, ServerGreet, ServerMessage, UploadDone, WireError};
+
+/// What a session expects next
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Phase {
+    /// Waiting for the server `ServerGreet`
+    AwaitServerGreet,
+    /// Waiting for the client `ClientGreet`
+    AwaitClientGreet,
+    /// Client is sending records, until `UploadDone`
+    Uploading,
+    /// Upload is complete, only `DelRecord`, `Ping` and `Pong` follow
+    Established,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Phase::AwaitServerGreet => "waiting for ServerGreet",
+            Phase::AwaitClientGreet => "waiting for ClientGreet",
+            Phase::Uploading => "uploading",
+            Phase::Established => "established",
+        })
+    }
+}
+
+/// Checks the messages of one session against the protocol order
+#[derive(Debug, Clone)]
+pub struct SessionChecker {
+    phase: Phase,
+    records: HashSet<u32>,
+}
+
+impl Default for SessionChecker {
+    fn default() -> Self {
+        SessionChecker { phase: Phase::AwaitServerGreet, records: HashSet::new() }
+    }
+}
+
+impl SessionChecker {
+    pub fn new() -> SessionChecker {
+        SessionChecker::default()
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// Return true if `recid` was added and not deleted
+    pub fn has_record(&self, recid: u32) -> bool {
+        self.records.contains(&recid)
+    }
+
+    /// Number of records currently added
+    pub fn records(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Check the next message sent in `direction` and advance the session.
+    /// An out of order message leaves the session unchanged.
+    pub fn check(&mut self, direction: Direction, msg: &Message) -> Result<(), WireError> {
+        let unexpected = || WireError::UnexpectedMessage { phase: self.phase, direction, msg_id: msg.msg_id() };
+        let sender = match msg {
+            Message::ServerGreet(_) | Message::Ping(_) => Some(Direction::ServerToClient),
+            Message::Unknown { .. } => None,
+            _ => Some(Direction::ClientToServer),
+        };
+        if sender.is_some_and(|sender| sender != direction) {
+            return Err(unexpected());
+        }
+
+        match (self.phase, msg) {
+            (_, Message::Ping(_) | Message::Pong(_) | Message::Unknown { .. }) => {},
+            (Phase::AwaitServerGreet, Message::ServerGreet(_)) => self.phase = Phase::AwaitClientGreet,
+            (Phase::AwaitClientGreet, Message::ClientGreet(_)) => self.phase = Phase::Uploading,
+            (Phase::Uploading, Message::AddRecord(msg)) => {
+                if msg.atype == AddRecordType::Alias as u8 {
+                    self.require_record(msg.recid)?;
+                } else {
+                    self.records.insert(msg.recid);
+                }
+            },
+            (Phase::Uploading, Message::AddInfo(msg)) => self.require_record(msg.recid)?,
+            (Phase::Uploading | Phase::Established, Message::DelRecord(msg)) => {
+                self.require_record(msg.recid)?;
+                self.records.remove(&msg.recid);
+            },
+            (Phase::Uploading, Message::UploadDone(_)) => self.phase = Phase::Established,
+            _ => return Err(unexpected()),
+        }
+        Ok(())
+    }
+
+    /// Check a message sent by the RecCaster
+    pub fn check_client(&mut self, msg: &ClientMessage) -> Result<(), WireError> {
+        self.check(Direction::ClientToServer, &msg.clone().into())
+    }
+
+    /// Check a message sent by the RecCeiver
+    pub fn check_server(&mut self, msg: &ServerMessage) -> Result<(), WireError> {
+        self.check(Direction::ServerToClient, &msg.clone().into())
+    }
+
+    fn require_record(&self, recid: u32) -> Result<(), WireError> {
+        if !self.records.contains(&recid) {
+            return Err(WireError::UnknownRecid(recid));
+        }
+        Ok(())
+    }
+}
+
+/// Session waiting for `ServerGreet`
+#[derive(Debug)]
+pub struct AwaitServerGreet;
+
+/// Session waiting for `ClientGreet`
+#[derive(Debug)]
+pub struct AwaitClientGreet;
+
+/// Session in the record upload
+#[derive(Debug)]
+pub struct Uploading;
+
+/// Session after `UploadDone`
+#[derive(Debug)]
+pub struct Established;
+
+/// Session whose phase is part of its type, only the messages allowed next can be passed in.
+/// The same methods work for the side sending and the side receiving each message.
+#[derive(Debug)]
+pub struct Session<S> {
+    checker: SessionChecker,
+    state: PhantomData<S>,
+}
+
+impl<S> Session<S> {
+    fn advance<T>(mut self, msg: &Message) -> Session<T> {
+        let direction = if matches!(msg, Message::ServerGreet(_)) { Direction::ServerToClient } else { Direction::ClientToServer };
+        self.checker.check(direction, msg).expect("the session type only allows the next message of the order");
+        Session { checker: self.checker, state: PhantomData }
+    }
+
+    /// Keepalive sent by the RecCeiver, allowed in every phase
+    pub fn ping(&mut self, msg: Ping) -> Message {
+        let msg = Message::Ping(msg);
+        self.checker.check(Direction::ServerToClient, &msg).expect("Ping is allowed in every phase");
+        msg
+    }
+
+    /// Keepalive reply of the RecCaster, allowed in every phase
+    pub fn pong(&mut self, msg: Pong) -> Message {
+        let msg = Message::Pong(msg);
+        self.checker.check(Direction::ClientToServer, &msg).expect("Pong is allowed in every phase");
+        msg
+    }
+
+    pub fn checker(&self) -> &SessionChecker {
+        &self.checker
+    }
+
+    /// Continue checking with the run time checker
+    pub fn into_checker(self) -> SessionChecker {
+        self.checker
+    }
+}
+
+impl Default for Session<AwaitServerGreet> {
+    fn default() -> Self {
+        Session { checker: SessionChecker::new(), state: PhantomData }
+    }
+}
+
+impl Session<AwaitServerGreet> {
+    pub fn new() -> Session<AwaitServerGreet> {
+        Session::default()
+    }
+
+    pub fn server_greet(self, msg: ServerGreet) -> (Session<AwaitClientGreet>, Message) {
+        let msg = Message::ServerGreet(msg);
+        (self.advance(&msg), msg)
+    }
+}
+
+impl Session<AwaitClientGreet> {
+    pub fn client_greet(self, msg: ClientGreet) -> (Session<Uploading>, Message) {
+        let msg = Message::ClientGreet(msg);
+        (self.advance(&msg), msg)
+    }
+}
+
+impl Session<Uploading> {
+    /// Add a record or an alias, an alias must refer to an added record
+    pub fn add_record(&mut self, msg: AddRecord) -> Result<Message, WireError> {
+        self.record_msg(Message::AddRecord(msg))
+    }
+
+    /// Add an info tag to an added record
+    pub fn add_info(&mut self, msg: AddInfo) -> Result<Message, WireError> {
+        self.record_msg(Message::AddInfo(msg))
+    }
+
+    /// Delete an added record
+    pub fn del_record(&mut self, msg: DelRecord) -> Result<Message, WireError> {
+        self.record_msg(Message::DelRecord(msg))
+    }
+
+    pub fn upload_done(self, msg: UploadDone) -> (Session<Established>, Message) {
+        let msg = Message::UploadDone(msg);
+        (self.advance(&msg), msg)
+    }
+
+    fn record_msg(&mut self, msg: Message) -> Result<Message, WireError> {
+        self.checker.check(Direction::ClientToServer, &msg)?;
+        Ok(msg)
+    }
+}
+
+impl Session<Established> {
+    /// Delete an uploaded record
+    pub fn del_record(&mut self, msg: DelRecord) -> Result<Message, WireError> {
+        let msg = Message::DelRecord(msg);
+        self.checker.check(Direction::ClientToServer, &msg)?;
+        Ok(msg)
+    }
+}
@@ -0,0 +1,130 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+//! `Arbitrary` impls and proptest strategies, enabled by the `arbitrary` feature.
+//! The feature needs Rust 1.88 or later, the minimum version of proptest.
+//!
+//! Generated messages are valid: strings are UTF-8 and fit their length fields, unknown messages never
+//! reuse a known ID, and announcements carry the magic number, so `decode(encode(m)) == m` with a default codec.
+
+use arbitrary::{Arbitrary, Unstructured};
+use bytes::Bytes;
+use proptest::{collection::vec, prelude::*};
+use std::net::{IpAddr, Ipv6Addr};
+
+use crate::{header::HEADER_LEN, types::canonical, AddInfo, AddRecord, Announcement, Message, MessageID, DEFAULT_MAX_FRAME_LEN, MAX_KEY_LEN, MAX_RNAME_LEN, MAX_RTYPE_LEN, MAX_VALUE_LEN};
+
+/// Largest body of an unknown message that a default codec accepts
+const MAX_UNKNOWN_BODY_LEN: usize = DEFAULT_MAX_FRAME_LEN - HEADER_LEN;
+
+/// Cut `string` to at most `max` bytes without splitting a character
+fn truncate(mut string: String, max: usize) -> Bytes {
+    let mut len = string.len().min(max);
+    while !string.is_char_boundary(len) {
+        len -= 1;
+    }
+    string.truncate(len);
+    Bytes::from(string)
+}
+
+fn unknown_msg_id(msg_id: u16) -> bool {
+    MessageID::try_from(msg_id).is_err()
+}
+
+fn arbitrary_text(u: &mut Unstructured<'_>, max: usize) -> arbitrary::Result<Bytes> {
+    Ok(truncate(String::arbitrary(u)?, max))
+}
+
+impl<'a> Arbitrary<'a> for AddRecord {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(AddRecord { recid: u.arbitrary()?, atype: u.arbitrary()?, rtype: arbitrary_text(u, MAX_RTYPE_LEN)?, rname: arbitrary_text(u, MAX_RNAME_LEN)? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for AddInfo {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(AddInfo { recid: u.arbitrary()?, key: arbitrary_text(u, MAX_KEY_LEN)?, value: arbitrary_text(u, MAX_VALUE_LEN)? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Message {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(match u.int_in_range(0..=8)? {
+            0 => Message::ServerGreet(u.arbitrary()?),
+            1 => Message::Ping(u.arbitrary()?),
+            2 => Message::ClientGreet(u.arbitrary()?),
+            3 => Message::Pong(u.arbitrary()?),
+            4 => Message::AddRecord(u.arbitrary()?),
+            5 => Message::DelRecord(u.arbitrary()?),
+            6 => Message::UploadDone(u.arbitrary()?),
+            7 => Message::AddInfo(u.arbitrary()?),
+            _ => {
+                let mut msg_id: u16 = u.arbitrary()?;
+                if !unknown_msg_id(msg_id) {
+                    // Move known IDs to an unused range rather than rejecting the input
+                    msg_id ^= 0x4000;
+                }
+                let len = u.int_in_range(0..=MAX_UNKNOWN_BODY_LEN)?;
+                Message::Unknown { msg_id, body: Bytes::copy_from_slice(u.bytes(len.min(u.len()))?) }
+            },
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Announcement {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Announcement::new(canonical(u.arbitrary()?), u.arbitrary()?, u.arbitrary()?))
+    }
+}
+
+/// UTF-8 text of at most `max` bytes
+pub fn text(max: usize) -> impl Strategy<Value = Bytes> {
+    // Mostly short strings, like real record names, with the occasional one up to the limit
+    let len = prop_oneof![4 => 0..=max.min(32), 1 => 0..=max];
+    len.prop_flat_map(|len| vec(any::<char>(), 0..=len)).prop_map(move |chars| truncate(chars.into_iter().collect(), max))
+}
+
+pub fn add_record() -> impl Strategy<Value = AddRecord> {
+    (any::<u32>(), any::<u8>(), text(MAX_RTYPE_LEN), text(MAX_RNAME_LEN)).prop_map(|(recid, atype, rtype, rname)| AddRecord { recid, atype, rtype, rname })
+}
+
+pub fn add_info() -> impl Strategy<Value = AddInfo> {
+    (any::<u32>(), text(MAX_KEY_LEN), text(MAX_VALUE_LEN)).prop_map(|(recid, key, value)| AddInfo { recid, key, value })
+}
+
+/// Message with an ID this crate does not know about
+pub fn unknown() -> impl Strategy<Value = Message> {
+    let body = prop_oneof![4 => vec(any::<u8>(), 0..=64), 1 => vec(any::<u8>(), 0..=MAX_UNKNOWN_BODY_LEN)];
+    (any::<u16>().prop_filter("known message ID", |msg_id| unknown_msg_id(*msg_id)), body)
+        .prop_map(|(msg_id, body)| Message::Unknown { msg_id, body: Bytes::from(body) })
+}
+
+/// Any message, every variant is generated
+pub fn message() -> impl Strategy<Value = Message> {
+    use crate::{ClientGreet, DelRecord, Ping, Pong, ServerGreet, UploadDone};
+
+    prop_oneof![
+        any::<u8>().prop_map(|server_type| Message::ServerGreet(ServerGreet { server_type })),
+        any::<u32>().prop_map(|nonce| Message::Ping(Ping { nonce })),
+        (any::<u8>(), any::<u8>(), any::<u32>()).prop_map(|(version, client_type, serv_key)| Message::ClientGreet(ClientGreet { version, client_type, serv_key })),
+        any::<u32>().prop_map(|nonce| Message::Pong(Pong { nonce })),
+        add_record().prop_map(Message::AddRecord),
+        any::<u32>().prop_map(|recid| Message::DelRecord(DelRecord { recid })),
+        Just(Message::UploadDone(UploadDone)),
+        add_info().prop_map(Message::AddInfo),
+        unknown(),
+    ]
+}
+
+/// Announcement for an IPv4 or IPv6 RecCeiver
+pub fn announcement() -> impl Strategy<Value = Announcement> {
+    let addr = prop_oneof![
+        any::<[u8; 4]>().prop_map(IpAddr::from),
+        any::<[u8; 16]>().prop_map(|octets| canonical(IpAddr::V6(Ipv6Addr::from(octets)))),
+    ];
+    (addr, any::<u16>(), any::<u32>()).prop_map(|(addr, port, key)| Announcement::new(addr, port, key))
+}
@@ -0,0 +1,92 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+//! `tokio_util` codec implementations, enabled by the `tokio` feature
+
+use bytes::BytesMut;
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{Announcement, ClientCodec, ClientMessage, Message, MessageCodec, ServerCodec, ServerMessage, WireError};
+
+/// Encoder and Decoder for UDP announcements, to be used with `tokio_util::udp::UdpFramed`.
+/// Each call to `decode` consumes one whole datagram.
+pub struct AnnouncementCodec;
+
+impl Encoder<Announcement> for AnnouncementCodec {
+    type Error = WireError;
+
+    fn encode(&mut self, msg: Announcement, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        msg.encode_to(dst);
+        Ok(())
+    }
+}
+
+impl Decoder for AnnouncementCodec {
+    type Item = Announcement;
+    type Error = WireError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.is_empty() {
+            return Ok(None);
+        }
+        // Take the datagram out first so a malformed one is dropped rather than decoded again
+        let datagram = src.split();
+        Announcement::decode(&datagram).map(Some)
+    }
+}
+
+impl Encoder<Message> for MessageCodec {
+    type Error = WireError;
+
+    fn encode(&mut self, msg: Message, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        dst.reserve(msg.encoded_len());
+        msg.encode_to(dst)
+    }
+}
+
+impl Decoder for MessageCodec {
+    type Item = Message;
+    type Error = WireError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.decode_message(src)
+    }
+}
+
+impl Encoder<ClientMessage> for ClientCodec {
+    type Error = WireError;
+
+    fn encode(&mut self, msg: ClientMessage, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.codec.encode(msg.into(), dst)
+    }
+}
+
+impl Decoder for ClientCodec {
+    type Item = ServerMessage;
+    type Error = WireError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.decode_message(src)
+    }
+}
+
+impl Encoder<ServerMessage> for ServerCodec {
+    type Error = WireError;
+
+    fn encode(&mut self, msg: ServerMessage, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.codec.encode(msg.into(), dst)
+    }
+}
+
+impl Decoder for ServerCodec {
+    type Item = ClientMessage;
+    type Error = WireError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.decode_message(src)
+    }
+}
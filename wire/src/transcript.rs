@@ -0,0 +1,375 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+//! Line based text format for recsync sessions, stable enough to write test scenarios by hand
+//! and to compare against captured traffic.
+//!
+//! ```text
+//! # Comments and blank lines are ignored
+//! S->C ServerGreet
+//! C->S ClientGreet key=0x12345678
+//! C->S AddRecord recid=100 type=ai name=DEV:TEMP
+//! C->S AddRecord recid=100 kind=alias type=ai name=DEV:TEMP:ALIAS
+//! C->S AddInfo recid=100 key=recordDesc value="Room temperature"
+//! C->S UploadDone
+//! S->C Ping nonce=7
+//! C->S Pong nonce=7
+//! C->S DelRecord recid=100
+//! S->C Unknown msg_id=0x0042 body=0102ff
+//! ```
+//!
+//! Strings that are empty or contain spaces, quotes, `#`, `\` or non printable characters are quoted,
+//! with `\"`, `\\`, `\n`, `\r`, `\t` and `\xNN` escapes. `\xNN` keeps bytes that are not valid UTF-8.
+//! Numbers are decimal or `0x` prefixed hexadecimal.
+//! The greetings also take `type=` and `version=` fields, which are written only when they are not zero.
+
+use bytes::Bytes;
+use std::{fmt, str::FromStr};
+
+use crate::{AddInfo, AddRecord, AddRecordType, ClientGreet, DelRecord, Message, Ping, Pong, ServerGreet, UploadDone, WireError};
+
+/// Which peer sent a message
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Direction {
+    /// `C->S`, RecCaster to RecCeiver
+    ClientToServer,
+    /// `S->C`, RecCeiver to RecCaster
+    ServerToClient,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Direction::ClientToServer => "C->S",
+            Direction::ServerToClient => "S->C",
+        })
+    }
+}
+
+/// A sequence of messages exchanged in one session
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Transcript {
+    pub entries: Vec<(Direction, Message)>,
+}
+
+impl Transcript {
+    pub fn new() -> Transcript {
+        Transcript::default()
+    }
+
+    pub fn push(&mut self, direction: Direction, msg: Message) {
+        self.entries.push((direction, msg));
+    }
+
+    /// Messages sent in one direction, in order
+    pub fn messages(&self, direction: Direction) -> impl Iterator<Item = &Message> {
+        self.entries.iter().filter(move |(dir, _)| *dir == direction).map(|(_, msg)| msg)
+    }
+}
+
+impl fmt::Display for Transcript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (direction, msg) in &self.entries {
+            writeln!(f, "{} {}", direction, msg)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Transcript {
+    type Err = WireError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut transcript = Transcript::new();
+        for (i, line) in text.lines().enumerate() {
+            let syntax = |reason| WireError::Syntax { line: i + 1, reason };
+            let mut tokens = tokenize(line).map_err(syntax)?.into_iter();
+            let Some(first) = tokens.next() else {
+                continue;
+            };
+            let direction = match first {
+                Token::Word(word) if word == "C->S" => Direction::ClientToServer,
+                Token::Word(word) if word == "S->C" => Direction::ServerToClient,
+                _ => return Err(syntax("expected C->S or S->C".to_string())),
+            };
+            let msg = parse_message(tokens).map_err(syntax)?;
+            transcript.push(direction, msg);
+        }
+        Ok(transcript)
+    }
+}
+
+/// One message without a direction, as in `AddRecord recid=100 type=ai name=FOO`
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Message::ServerGreet(msg) => {
+                write!(f, "ServerGreet")?;
+                if msg.server_type != 0 {
+                    write!(f, " type={}", msg.server_type)?;
+                }
+                Ok(())
+            },
+            Message::ClientGreet(msg) => {
+                write!(f, "ClientGreet key={:#010x}", msg.serv_key)?;
+                if msg.version != 0 {
+                    write!(f, " version={}", msg.version)?;
+                }
+                if msg.client_type != 0 {
+                    write!(f, " type={}", msg.client_type)?;
+                }
+                Ok(())
+            },
+            Message::Ping(msg) => write!(f, "Ping nonce={}", msg.nonce),
+            Message::Pong(msg) => write!(f, "Pong nonce={}", msg.nonce),
+            Message::AddRecord(msg) => {
+                write!(f, "AddRecord recid={}", msg.recid)?;
+                match msg.atype {
+                    0 => {},
+                    1 => write!(f, " kind=alias")?,
+                    atype => write!(f, " kind={}", atype)?,
+                }
+                write!(f, " type={} name={}", Text(&msg.rtype), Text(&msg.rname))
+            },
+            Message::AddInfo(msg) => write!(f, "AddInfo recid={} key={} value={}", msg.recid, Text(&msg.key), Text(&msg.value)),
+            Message::DelRecord(msg) => write!(f, "DelRecord recid={}", msg.recid),
+            Message::UploadDone(_) => write!(f, "UploadDone"),
+            Message::Unknown { msg_id, body } => {
+                write!(f, "Unknown msg_id={:#06x} body=", msg_id)?;
+                if body.is_empty() {
+                    return write!(f, "\"\"");
+                }
+                body.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+            },
+        }
+    }
+}
+
+impl FromStr for Message {
+    type Err = WireError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let syntax = |reason| WireError::Syntax { line: 1, reason };
+        parse_message(tokenize(line).map_err(syntax)?.into_iter()).map_err(syntax)
+    }
+}
+
+/// String field, quoted and escaped when needed
+struct Text<'a>(&'a [u8]);
+
+impl fmt::Display for Text<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bare = !self.0.is_empty() && self.0.iter().all(|byte| byte.is_ascii_graphic() && !matches!(byte, b'"' | b'\\' | b'#'));
+        if bare {
+            // Checked to be ASCII
+            return f.write_str(std::str::from_utf8(self.0).map_err(|_| fmt::Error)?);
+        }
+        f.write_str("\"")?;
+        for (valid, invalid) in utf8_chunks(self.0) {
+            for c in valid.chars() {
+                match c {
+                    '"' => f.write_str("\\\"")?,
+                    '\\' => f.write_str("\\\\")?,
+                    '\n' => f.write_str("\\n")?,
+                    '\r' => f.write_str("\\r")?,
+                    '\t' => f.write_str("\\t")?,
+                    c if c.is_control() => c.encode_utf8(&mut [0; 4]).bytes().try_for_each(|byte| write!(f, "\\x{:02x}", byte))?,
+                    c => write!(f, "{}", c)?,
+                }
+            }
+            for byte in invalid {
+                write!(f, "\\x{:02x}", byte)?;
+            }
+        }
+        f.write_str("\"")
+    }
+}
+
+/// Split `bytes` into runs of valid UTF-8, each followed by the invalid bytes after it
+fn utf8_chunks(mut bytes: &[u8]) -> impl Iterator<Item = (&str, &[u8])> {
+    std::iter::from_fn(move || {
+        if bytes.is_empty() {
+            return None;
+        }
+        let (valid_len, invalid_len) = match std::str::from_utf8(bytes) {
+            Ok(_) => (bytes.len(), 0),
+            Err(err) => (err.valid_up_to(), err.error_len().unwrap_or(bytes.len() - err.valid_up_to())),
+        };
+        let (valid, rest) = bytes.split_at(valid_len);
+        let (invalid, rest) = rest.split_at(invalid_len);
+        bytes = rest;
+        Some((std::str::from_utf8(valid).unwrap_or_default(), invalid))
+    })
+}
+
+#[derive(Debug)]
+enum Token {
+    Word(String),
+    Field(String, Vec<u8>),
+}
+
+/// Split a line into words and `key=value` fields, stopping at a `#` comment
+fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        match chars.peek() {
+            None | Some('#') => return Ok(tokens),
+            Some(_) => {},
+        }
+        let mut word = String::new();
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '=') {
+            word.push(c);
+        }
+        if chars.next_if_eq(&'=').is_none() {
+            tokens.push(Token::Word(word));
+            continue;
+        }
+        let mut value = Vec::new();
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next() {
+                    None => return Err(format!("unterminated string in {}", word)),
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some('"') => value.push(b'"'),
+                        Some('\\') => value.push(b'\\'),
+                        Some('n') => value.push(b'\n'),
+                        Some('r') => value.push(b'\r'),
+                        Some('t') => value.push(b'\t'),
+                        Some('x') => {
+                            let hex: String = chars.by_ref().take(2).collect();
+                            let byte = u8::from_str_radix(&hex, 16).map_err(|_| format!("bad \\x escape in {}", word))?;
+                            value.push(byte);
+                        },
+                        _ => return Err(format!("bad escape in {}", word)),
+                    },
+                    Some(c) => value.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+                }
+            }
+            if chars.peek().is_some_and(|c| !c.is_whitespace()) {
+                return Err(format!("expected a space after the string in {}", word));
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                value.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            }
+        }
+        tokens.push(Token::Field(word, value));
+    }
+}
+
+/// Fields of one message, each has to be used exactly once
+struct Fields(Vec<(String, Vec<u8>)>);
+
+impl Fields {
+    fn take(&mut self, name: &str) -> Result<Vec<u8>, String> {
+        self.take_opt(name)?.ok_or_else(|| format!("missing {}", name))
+    }
+
+    fn take_opt(&mut self, name: &str) -> Result<Option<Vec<u8>>, String> {
+        match self.0.iter().position(|(field, _)| field == name) {
+            Some(i) => Ok(Some(self.0.remove(i).1)),
+            None => Ok(None),
+        }
+    }
+
+    fn text(&mut self, name: &str) -> Result<Bytes, String> {
+        self.take(name).map(Bytes::from)
+    }
+
+    fn number<T: TryFrom<u64>>(&mut self, name: &str) -> Result<T, String> {
+        let value = self.take(name)?;
+        parse_number(&value).ok_or_else(|| format!("bad number for {}: {}", name, String::from_utf8_lossy(&value)))
+    }
+
+    /// Number of an optional field, zero when it is left out
+    fn number_or_zero<T: TryFrom<u64> + Default>(&mut self, name: &str) -> Result<T, String> {
+        if self.0.iter().any(|(field, _)| field == name) {
+            self.number(name)
+        } else {
+            Ok(T::default())
+        }
+    }
+
+    fn finish(self) -> Result<(), String> {
+        match self.0.first() {
+            Some((field, _)) => Err(format!("unexpected field {}", field)),
+            None => Ok(()),
+        }
+    }
+}
+
+fn parse_number<T: TryFrom<u64>>(value: &[u8]) -> Option<T> {
+    let value = std::str::from_utf8(value).ok()?;
+    let number = match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+        None => value.parse().ok()?,
+    };
+    T::try_from(number).ok()
+}
+
+fn parse_hex(value: &[u8]) -> Option<Vec<u8>> {
+    if value.len() % 2 != 0 {
+        return None;
+    }
+    value.chunks(2).map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()).collect()
+}
+
+fn parse_message(mut tokens: impl Iterator<Item = Token>) -> Result<Message, String> {
+    let name = match tokens.next() {
+        Some(Token::Word(name)) => name,
+        Some(Token::Field(field, _)) => return Err(format!("expected a message name, found {}=", field)),
+        None => return Err("expected a message name".to_string()),
+    };
+    let mut fields = Vec::new();
+    for token in tokens {
+        match token {
+            Token::Field(field, _) if fields.iter().any(|(seen, _)| *seen == field) => return Err(format!("duplicate field {}", field)),
+            Token::Field(field, value) => fields.push((field, value)),
+            Token::Word(word) => return Err(format!("expected field=value, found {}", word)),
+        }
+    }
+    let mut fields = Fields(fields);
+
+    let msg = match name.as_str() {
+        "ServerGreet" => Message::ServerGreet(ServerGreet { server_type: fields.number_or_zero("type")? }),
+        "ClientGreet" => Message::ClientGreet(ClientGreet { version: fields.number_or_zero("version")?, client_type: fields.number_or_zero("type")?, serv_key: fields.number("key")? }),
+        "Ping" => Message::Ping(Ping { nonce: fields.number("nonce")? }),
+        "Pong" => Message::Pong(Pong { nonce: fields.number("nonce")? }),
+        "AddRecord" => {
+            let recid = fields.number("recid")?;
+            let atype = match fields.take_opt("kind")?.as_deref() {
+                None | Some(b"record") => AddRecordType::Record as u8,
+                Some(b"alias") => AddRecordType::Alias as u8,
+                Some(kind) => parse_number(kind).ok_or_else(|| format!("bad kind {}", String::from_utf8_lossy(kind)))?,
+            };
+            let msg = AddRecord { recid, atype, rtype: fields.text("type")?, rname: fields.text("name")? };
+            msg.validate().map_err(|err| err.to_string())?;
+            Message::AddRecord(msg)
+        },
+        "AddInfo" => {
+            let msg = AddInfo { recid: fields.number("recid")?, key: fields.text("key")?, value: fields.text("value")? };
+            msg.validate().map_err(|err| err.to_string())?;
+            Message::AddInfo(msg)
+        },
+        "DelRecord" => Message::DelRecord(DelRecord { recid: fields.number("recid")? }),
+        "UploadDone" => Message::UploadDone(UploadDone),
+        "Unknown" => {
+            let msg_id = fields.number("msg_id")?;
+            let body = fields.take("body")?;
+            let body = parse_hex(&body).ok_or_else(|| format!("bad hex body {}", String::from_utf8_lossy(&body)))?;
+            let msg = Message::Unknown { msg_id, body: Bytes::from(body) };
+            msg.validate().map_err(|err| err.to_string())?;
+            msg
+        },
+        name => return Err(format!("unknown message {}", name)),
+    };
+    fields.finish()?;
+    Ok(msg)
+}
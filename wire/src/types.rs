@@ -5,23 +5,166 @@
 // You must comply with both licenses to use, modify, or distribute this software.
 // See the LICENSE file for details.
 
-use std::net::Ipv4Addr;
+use bytes::{BufMut, Bytes, BytesMut};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6};
+
+use crate::{schema::Body, WireError, MSG_MAGIC_ID};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 /// AddRecord message type
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum AddRecordType {
     Record = 0,
     Alias = 1,
 }
 
+/// Size of an announcement datagram on the wire
+pub const ANNOUNCEMENT_LEN: usize = 16;
+
+/// Announcement format version
+pub const ANNOUNCEMENT_VERSION: u8 = 0;
+
+/// Size of an extended announcement datagram carrying an IPv6 address
+pub const ANNOUNCEMENT_V6_LEN: usize = 28;
+
+/// Extended announcement format version, only understood by recsync-rs
+pub const ANNOUNCEMENT_V6_VERSION: u8 = 1;
+
 /// UDP Announcement message structure
-#[derive(Debug)]
+///
+/// Wire layout (big endian), version 0:
+/// `id: u16 | version: u8 | reserved: u8 | server_addr: [u8; 4] | server_port: u16 | reserved: u16 | server_key: u32`
+///
+/// Version 1 is the same with a 16 bytes IPv6 `server_addr`. It is only sent for IPv6 addresses,
+/// so RecCeivers on IPv4 stay compatible with the C RecCaster.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Announcement {
     pub id: u16,
-    pub server_addr: Ipv4Addr,
+    pub server_addr: IpAddr,
     pub server_port: u16,
     pub server_key: u32,
 }
 
+impl Announcement {
+    pub fn new(server_addr: impl Into<IpAddr>, server_port: u16, server_key: u32) -> Announcement {
+        Announcement { id: MSG_MAGIC_ID, server_addr: server_addr.into(), server_port, server_key }
+    }
+
+    /// Parse an announcement datagram.
+    /// Reserved bytes are ignored like the C implementation does, and so is anything past the announcement.
+    /// A version 1 IPv4-mapped address is returned as IPv4.
+    pub fn decode(data: &[u8]) -> Result<Announcement, WireError> {
+        if data.len() < ANNOUNCEMENT_LEN {
+            return Err(WireError::Truncated { needed: ANNOUNCEMENT_LEN, available: data.len() });
+        }
+
+        let id = u16::from_be_bytes([data[0], data[1]]);
+        // Checking if the ID is 'RC'
+        if id != MSG_MAGIC_ID {
+            return Err(WireError::BadMagic(id));
+        }
+
+        let (server_addr, rest) = match data[2] {
+            ANNOUNCEMENT_VERSION => (IpAddr::V4(Ipv4Addr::new(data[4], data[5], data[6], data[7])), &data[8..]),
+            ANNOUNCEMENT_V6_VERSION => {
+                if data.len() < ANNOUNCEMENT_V6_LEN {
+                    return Err(WireError::Truncated { needed: ANNOUNCEMENT_V6_LEN, available: data.len() });
+                }
+                let octets: [u8; 16] = data[4..20].try_into().expect("length checked");
+                (canonical(IpAddr::V6(Ipv6Addr::from(octets))), &data[20..])
+            },
+            version => return Err(WireError::UnsupportedVersion(version)),
+        };
+        let server_port = u16::from_be_bytes([rest[0], rest[1]]);
+        let server_key = u32::from_be_bytes([rest[4], rest[5], rest[6], rest[7]]);
+
+        Ok(Announcement { id, server_addr, server_port, server_key })
+    }
+
+    /// Format version used to encode this announcement, version 1 only for IPv6 addresses.
+    /// IPv4-mapped addresses are sent as IPv4, as they are decoded.
+    pub fn version(&self) -> u8 {
+        match canonical(self.server_addr) {
+            IpAddr::V4(_) => ANNOUNCEMENT_VERSION,
+            IpAddr::V6(_) => ANNOUNCEMENT_V6_VERSION,
+        }
+    }
+
+    /// Number of bytes [`Announcement::encode_to`] writes
+    pub fn encoded_len(&self) -> usize {
+        match canonical(self.server_addr) {
+            IpAddr::V4(_) => ANNOUNCEMENT_LEN,
+            IpAddr::V6(_) => ANNOUNCEMENT_V6_LEN,
+        }
+    }
+
+    /// Write the announcement datagram to `dst`, reserved bytes are zeroed
+    pub fn encode_to(&self, dst: &mut impl BufMut) {
+        dst.put_u16(self.id);
+        dst.put_u8(self.version());
+        dst.put_u8(0); // Reserved
+        match canonical(self.server_addr) {
+            IpAddr::V4(addr) => dst.put_slice(&addr.octets()),
+            IpAddr::V6(addr) => dst.put_slice(&addr.octets()),
+        }
+        dst.put_u16(self.server_port);
+        dst.put_u16(0); // Reserved
+        dst.put_u32(self.server_key);
+    }
+
+    /// Return the announcement datagram as BytesMut
+    pub fn encode(&self) -> BytesMut {
+        let mut buf = BytesMut::with_capacity(self.encoded_len());
+        self.encode_to(&mut buf);
+        buf
+    }
+
+    /// True if the RecCeiver asks casters to connect back to the address the datagram came from:
+    /// the IPv4 broadcast address, or the unspecified address in the IPv6 format
+    pub fn is_reply_to_sender(&self) -> bool {
+        match self.server_addr {
+            IpAddr::V4(addr) => addr.is_broadcast(),
+            IpAddr::V6(addr) => addr.is_unspecified(),
+        }
+    }
+
+    /// Address to connect to, the sender address `src` when the RecCeiver asks for it, see [`Announcement::is_reply_to_sender`].
+    /// IPv6 senders keep their scope, and so do link-local addresses announced by them, which are only valid on the link the datagram came in on.
+    /// IPv4-mapped IPv6 senders, as seen on dual-stack sockets, are turned back into IPv4 addresses.
+    pub fn resolve(&self, src: SocketAddr) -> SocketAddr {
+        let reply_to_sender = self.is_reply_to_sender();
+        let addr = if reply_to_sender { src.ip() } else { self.server_addr };
+        match (canonical(addr), src) {
+            (IpAddr::V6(addr), SocketAddr::V6(src)) if reply_to_sender || is_link_local(addr) => {
+                SocketAddr::V6(SocketAddrV6::new(addr, self.server_port, 0, src.scope_id()))
+            },
+            (addr, _) => SocketAddr::new(addr, self.server_port),
+        }
+    }
+
+    /// Address and port to connect to
+    pub fn server(&self) -> SocketAddr {
+        SocketAddr::new(self.server_addr, self.server_port)
+    }
+}
+
+/// Turn IPv4-mapped IPv6 addresses back into IPv4 addresses
+pub(crate) fn canonical(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(addr, IpAddr::V4),
+        IpAddr::V4(_) => addr,
+    }
+}
+
+/// True for fe80::/10 addresses, which need a scope to be connected to
+fn is_link_local(addr: Ipv6Addr) -> bool {
+    addr.segments()[0] & 0xffc0 == 0xfe80
+}
+
 /// Messages ID
 #[derive(Copy, Clone)]
 #[repr(u16)]
@@ -36,18 +179,20 @@ pub enum MessageID {
     AddInfo = 0x0006,
 }
 
-impl From<u16> for MessageID {
-    fn from(value: u16) -> Self {
+impl TryFrom<u16> for MessageID {
+    type Error = WireError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
         match value {
-            0x8001 => MessageID::ServerGreet,
-            0x0001 => MessageID::ClientGreet,
-            0x8002 => MessageID::Ping,
-            0x0002 => MessageID::Pong,
-            0x0003 => MessageID::AddRecord,
-            0x0004 => MessageID::DelRecord,
-            0x0005 => MessageID::UploadDone,
-            0x0006 => MessageID::AddInfo,
-            _ => unimplemented!("Unknown Message ID"),
+            0x8001 => Ok(MessageID::ServerGreet),
+            0x0001 => Ok(MessageID::ClientGreet),
+            0x8002 => Ok(MessageID::Ping),
+            0x0002 => Ok(MessageID::Pong),
+            0x0003 => Ok(MessageID::AddRecord),
+            0x0004 => Ok(MessageID::DelRecord),
+            0x0005 => Ok(MessageID::UploadDone),
+            0x0006 => Ok(MessageID::AddInfo),
+            _ => Err(WireError::UnknownMessageId(value)),
         }
     }
 }
@@ -69,52 +214,124 @@ impl From<MessageID> for u16 {
 
 // Define all the message structs and enums here
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct ServerGreet;
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct ServerGreet {
+    /// Kind of RecCeiver, 0 for the reference implementation
+    pub server_type: u8,
+}
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Ping {
     pub nonce: u32,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct ClientGreet {
+    /// Protocol version spoken by the RecCaster, 0 is the only one defined
+    pub version: u8,
+    /// Kind of RecCaster, 0 for the reference implementation
+    pub client_type: u8,
     pub serv_key: u32,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Pong {
     pub nonce: u32,
 }
 
+/// Maximum length of a record type, its length is sent as a `u8`
+pub const MAX_RTYPE_LEN: usize = u8::MAX as usize;
+
+/// Maximum length of a record name or alias, its length is sent as a `u16`
+pub const MAX_RNAME_LEN: usize = u16::MAX as usize;
+
+/// Maximum length of an info tag key, its length is sent as a `u8`
+pub const MAX_KEY_LEN: usize = u8::MAX as usize;
+
+/// Maximum length of an info tag value, its length is sent as a `u16`
+pub const MAX_VALUE_LEN: usize = u16::MAX as usize;
+
+/// Return an error if `value` does not fit in a length field that holds at most `max`
+pub(crate) fn check_len(field: &'static str, value: &[u8], max: usize) -> Result<(), WireError> {
+    if value.len() > max {
+        return Err(WireError::FieldTooLong { field, len: value.len(), max });
+    }
+    Ok(())
+}
+
+/// AddRecord message, the type and name lengths are derived from the strings when encoding.
+/// Strings are held as shared `Bytes` so cloning or decoding a message does not copy them.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct AddRecord {
     pub recid: u32,
     pub atype: u8,
-    pub rtlen: u8,
-    pub rnlen: u16,
-    pub rtype: String,
-    pub rname: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_text"))]
+    pub rtype: Bytes,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_text"))]
+    pub rname: Bytes,
+}
+
+impl AddRecord {
+    pub fn new(recid: u32, kind: AddRecordType, rtype: impl Into<Bytes>, rname: impl Into<Bytes>) -> Result<AddRecord, WireError> {
+        let msg = AddRecord { recid, atype: kind as u8, rtype: rtype.into(), rname: rname.into() };
+        msg.validate()?;
+        Ok(msg)
+    }
+
+    /// Check that the type and name fit in their length fields
+    pub fn validate(&self) -> Result<(), WireError> {
+        Body::validate(self)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct DelRecord {
     pub recid: u32,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct UploadDone;
 
+/// AddInfo message, the key and value lengths are derived from the strings when encoding.
+/// Strings are held as shared `Bytes` so cloning or decoding a message does not copy them.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct AddInfo {
     pub recid: u32,
-    pub keylen: u8,
-    pub valen: u16,
-    pub key: String,
-    pub value: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_text"))]
+    pub key: Bytes,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_text"))]
+    pub value: Bytes,
+}
+
+impl AddInfo {
+    pub fn new(recid: u32, key: impl Into<Bytes>, value: impl Into<Bytes>) -> Result<AddInfo, WireError> {
+        let msg = AddInfo { recid, key: key.into(), value: value.into() };
+        msg.validate()?;
+        Ok(msg)
+    }
+
+    /// Check that the key and value fit in their length fields
+    pub fn validate(&self) -> Result<(), WireError> {
+        Body::validate(self)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Message {
     ServerGreet(ServerGreet),
     Ping(Ping),
@@ -124,4 +341,10 @@ pub enum Message {
     DelRecord(DelRecord),
     UploadDone(UploadDone),
     AddInfo(AddInfo),
+    /// Message with an ID this crate does not know about, kept as its raw body
+    Unknown {
+        msg_id: u16,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_text"))]
+        body: Bytes,
+    },
 }
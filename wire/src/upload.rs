@@ -0,0 +1,114 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+use bytes::{BufMut, Bytes, BytesMut};
+use std::{io::IoSlice, ops::Range};
+
+use crate::{session::Phase, AddInfo, AddRecord, AddRecordType, ClientMessage, Direction, Message, UploadDone, WireError};
+
+/// All the messages a RecCaster sends after the greeting, encoded as a batch.
+/// Messages are checked when they are added, so encoding cannot fail, and an `UploadDone` is always sent last.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Upload {
+    msgs: Vec<Message>,
+    encoded_len: usize,
+}
+
+impl Upload {
+    pub fn new() -> Upload {
+        Upload::default()
+    }
+
+    /// Add an AddRecord, AddInfo or DelRecord message.
+    /// It fails with [`WireError::UnexpectedMessage`] for other messages, and if a string field is too long for the wire format.
+    pub fn push(&mut self, msg: ClientMessage) -> Result<(), WireError> {
+        let msg = match msg {
+            ClientMessage::AddRecord(_) | ClientMessage::AddInfo(_) | ClientMessage::DelRecord(_) => Message::from(msg),
+            msg => {
+                let msg_id = Message::from(msg).msg_id();
+                return Err(WireError::UnexpectedMessage { phase: Phase::Uploading, direction: Direction::ClientToServer, msg_id });
+            },
+        };
+        msg.validate()?;
+        self.encoded_len += msg.encoded_len();
+        self.msgs.push(msg);
+        Ok(())
+    }
+
+    /// Add an AddRecord message
+    pub fn add_record(&mut self, recid: u32, kind: AddRecordType, rtype: impl Into<Bytes>, rname: impl Into<Bytes>) -> Result<(), WireError> {
+        self.push(ClientMessage::AddRecord(AddRecord::new(recid, kind, rtype, rname)?))
+    }
+
+    /// Add an AddInfo message
+    pub fn add_info(&mut self, recid: u32, key: impl Into<Bytes>, value: impl Into<Bytes>) -> Result<(), WireError> {
+        self.push(ClientMessage::AddInfo(AddInfo::new(recid, key, value)?))
+    }
+
+    /// Messages added so far, without the final `UploadDone`
+    pub fn messages(&self) -> &[Message] {
+        &self.msgs
+    }
+
+    /// Number of bytes the whole upload takes on the wire, `UploadDone` included
+    pub fn encoded_len(&self) -> usize {
+        self.encoded_len + Message::UploadDone(UploadDone).encoded_len()
+    }
+
+    /// Write the whole upload to `dst`
+    pub fn encode_to(&self, dst: &mut impl BufMut) {
+        for msg in self.msgs.iter().chain([&Message::UploadDone(UploadDone)]) {
+            msg.encode_fixed(dst);
+            msg.for_each_text(|field| dst.put_slice(field));
+        }
+    }
+
+    /// Return the whole upload in one contiguous buffer
+    pub fn encode(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.encoded_len());
+        self.encode_to(&mut buf);
+        buf.freeze()
+    }
+
+    /// Return the upload as slices for vectored writes, without copying the string fields.
+    /// Headers and fixed size fields are written to `scratch`, which the slices borrow.
+    pub fn io_slices<'a>(&'a self, scratch: &'a mut BytesMut) -> Vec<IoSlice<'a>> {
+        enum Part<'a> {
+            Scratch(Range<usize>),
+            Field(&'a [u8]),
+        }
+
+        fn push_fixed(parts: &mut Vec<Part<'_>>, scratch: &mut BytesMut, msg: &Message) {
+            let start = scratch.len();
+            msg.encode_fixed(scratch);
+            match parts.last_mut() {
+                // Consecutive fixed parts are contiguous in the scratch buffer
+                Some(Part::Scratch(range)) => range.end = scratch.len(),
+                _ => parts.push(Part::Scratch(start..scratch.len())),
+            }
+        }
+
+        scratch.clear();
+        scratch.reserve(self.msgs.iter().map(Message::fixed_len).sum::<usize>() + Message::UploadDone(UploadDone).fixed_len());
+        let mut parts = Vec::new();
+        for msg in &self.msgs {
+            push_fixed(&mut parts, scratch, msg);
+            msg.for_each_text(|field| {
+                if !field.is_empty() {
+                    parts.push(Part::Field(&field[..]));
+                }
+            });
+        }
+        push_fixed(&mut parts, scratch, &Message::UploadDone(UploadDone));
+
+        let scratch = &scratch[..];
+        parts.into_iter().map(|part| match part {
+            Part::Scratch(range) => IoSlice::new(&scratch[range]),
+            Part::Field(field) => IoSlice::new(field),
+        }).collect()
+    }
+}
@@ -0,0 +1,120 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6};
+
+use bytes::BytesMut;
+use tokio_util::codec::Decoder;
+use wire::{Announcement, AnnouncementCodec, WireError};
+
+const DATAGRAM: [u8; 16] = [0x52, 0x43, 0x00, 0x00, 10, 0, 0, 1, 0x9c, 0x40, 0x00, 0x00, 0xde, 0xad, 0xbe, 0xef];
+
+#[test]
+fn decode_encode() {
+    let msg = Announcement::decode(&DATAGRAM).unwrap();
+    assert_eq!(msg, Announcement::new(Ipv4Addr::new(10, 0, 0, 1), 40000, 0xdeadbeef));
+    assert_eq!(&msg.encode()[..], &DATAGRAM[..]);
+}
+
+#[test]
+fn reserved_bytes_are_ignored() {
+    let mut data = DATAGRAM;
+    data[3] = 0xff;
+    data[10] = 0xff;
+    data[11] = 0xff;
+    assert_eq!(Announcement::decode(&data).unwrap(), Announcement::decode(&DATAGRAM).unwrap());
+}
+
+#[test]
+fn rejects_malformed() {
+    assert!(matches!(Announcement::decode(&DATAGRAM[..15]), Err(WireError::Truncated { needed: 16, available: 15 })));
+    let mut data = DATAGRAM;
+    data[0] = 0;
+    assert!(matches!(Announcement::decode(&data), Err(WireError::BadMagic(0x0043))));
+    let mut data = DATAGRAM;
+    data[2] = 2;
+    assert!(matches!(Announcement::decode(&data), Err(WireError::UnsupportedVersion(2))));
+    let mut data = DATAGRAM;
+    data[2] = 1;
+    assert!(matches!(Announcement::decode(&data), Err(WireError::Truncated { needed: 28, available: 16 })));
+}
+
+#[test]
+fn broadcast_address_is_substituted() {
+    let src: SocketAddr = "192.168.1.20:5049".parse().unwrap();
+    let server = Announcement::new(Ipv4Addr::BROADCAST, 40000, 1).resolve(src);
+    assert_eq!(server, "192.168.1.20:40000".parse().unwrap());
+
+    let server = Announcement::new(Ipv4Addr::new(10, 0, 0, 1), 40000, 1).resolve(src);
+    assert_eq!(server, "10.0.0.1:40000".parse().unwrap());
+}
+
+#[test]
+fn ipv6_announcement() {
+    let addr: Ipv6Addr = "fd00::1".parse().unwrap();
+    let msg = Announcement::new(addr, 40000, 0xdeadbeef);
+    let encoded = msg.encode();
+    assert_eq!(encoded.len(), 28);
+    assert_eq!(&encoded[..4], &[0x52, 0x43, 0x01, 0x00]);
+    assert_eq!(&encoded[4..20], &addr.octets());
+    assert_eq!(&encoded[20..], &DATAGRAM[8..]);
+    assert_eq!(Announcement::decode(&encoded).unwrap(), msg);
+
+}
+
+#[test]
+fn mapped_ipv4_address_uses_the_original_format() {
+    let mapped = Announcement::new(Ipv4Addr::new(10, 0, 0, 1).to_ipv6_mapped(), 40000, 0xdeadbeef);
+    assert_eq!(mapped.version(), 0);
+    assert_eq!(mapped.encoded_len(), 16);
+    assert_eq!(&mapped.encode()[..], &DATAGRAM[..]);
+    let decoded = Announcement::decode(&mapped.encode()).unwrap();
+    assert_eq!(decoded, Announcement::new(Ipv4Addr::new(10, 0, 0, 1), 40000, 0xdeadbeef));
+
+    // Also when a version 1 datagram carries one
+    let mut datagram = vec![0x52, 0x43, 0x01, 0x00];
+    datagram.extend_from_slice(&Ipv4Addr::new(10, 0, 0, 1).to_ipv6_mapped().octets());
+    datagram.extend_from_slice(&DATAGRAM[8..]);
+    let decoded = Announcement::decode(&datagram).unwrap();
+    assert_eq!(decoded.server_addr, Ipv4Addr::new(10, 0, 0, 1));
+    assert_eq!(&decoded.encode()[..], &DATAGRAM[..]);
+}
+
+#[test]
+fn unspecified_ipv6_address_is_substituted() {
+    let src: SocketAddr = "[fd00::20]:5049".parse().unwrap();
+    let server = Announcement::new(Ipv6Addr::UNSPECIFIED, 40000, 1).resolve(src);
+    assert_eq!(server, "[fd00::20]:40000".parse().unwrap());
+
+    // Dual-stack sockets report IPv4 senders as mapped addresses
+    let src: SocketAddr = "[::ffff:192.168.1.20]:5049".parse().unwrap();
+    let server = Announcement::new(Ipv4Addr::BROADCAST, 40000, 1).resolve(src);
+    assert_eq!(server.ip(), IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20)));
+}
+
+#[test]
+fn link_local_senders_keep_their_scope() {
+    let src = SocketAddr::V6(SocketAddrV6::new("fe80::20".parse().unwrap(), 5049, 0, 3));
+    let server = Announcement::new(Ipv6Addr::UNSPECIFIED, 40000, 1).resolve(src);
+    assert_eq!(server, SocketAddr::V6(SocketAddrV6::new("fe80::20".parse().unwrap(), 40000, 0, 3)));
+
+    // A link-local address announced explicitly is on the link the datagram came in on
+    let server = Announcement::new("fe80::30".parse::<Ipv6Addr>().unwrap(), 40000, 1).resolve(src);
+    assert_eq!(server, SocketAddr::V6(SocketAddrV6::new("fe80::30".parse().unwrap(), 40000, 0, 3)));
+
+    // Global addresses do not need one
+    let server = Announcement::new("fd00::30".parse::<Ipv6Addr>().unwrap(), 40000, 1).resolve(src);
+    assert_eq!(server, "[fd00::30]:40000".parse().unwrap());
+}
+
+#[test]
+fn codec_drops_malformed_datagram() {
+    let mut buf = BytesMut::from(&DATAGRAM[..10]);
+    assert!(AnnouncementCodec.decode(&mut buf).is_err());
+    assert!(buf.is_empty());
+    assert!(AnnouncementCodec.decode(&mut buf).unwrap().is_none());
+}
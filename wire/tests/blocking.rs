@@ -0,0 +1,70 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+use std::{io::{Cursor, ErrorKind}, net::{Ipv4Addr, UdpSocket}};
+
+use bytes::Bytes;
+use wire::{read_message, write_message, AddRecord, AddRecordType, Announcement, AnnouncementReader, Message, MessageCodec, Ping, ServerGreet, UnknownPolicy, WireError};
+
+#[test]
+fn write_then_read() {
+    let msgs = vec![
+        Message::ServerGreet(ServerGreet::default()),
+        Message::AddRecord(AddRecord::new(100, AddRecordType::Record, "ai", "DEV:TEMP").unwrap()),
+        Message::Ping(Ping { nonce: 3 }),
+    ];
+    let mut buf = Vec::new();
+    for msg in &msgs {
+        write_message(&mut buf, msg).unwrap();
+    }
+
+    let mut src = Cursor::new(buf);
+    for msg in &msgs {
+        assert_eq!(&read_message(&mut src).unwrap(), msg);
+    }
+    assert!(matches!(read_message(&mut src), Err(WireError::Io(err)) if err.kind() == ErrorKind::UnexpectedEof));
+}
+
+#[test]
+fn read_applies_codec_checks() {
+    let mut src = Cursor::new(b"AB\x80\x02\x00\x00\x00\x04\x00\x00\x00\x01".to_vec());
+    assert!(matches!(read_message(&mut src), Err(WireError::BadMagic(0x4142))));
+
+    let mut buf = Vec::new();
+    write_message(&mut buf, &Message::Unknown { msg_id: 0x0042, body: Bytes::from_static(&[1, 2]) }).unwrap();
+    write_message(&mut buf, &Message::Ping(Ping { nonce: 5 })).unwrap();
+    let mut codec = MessageCodec::new();
+    codec.set_unknown_policy(UnknownPolicy::Skip);
+    assert_eq!(codec.read_message(&mut Cursor::new(buf)).unwrap(), Message::Ping(Ping { nonce: 5 }));
+
+    let mut src = Cursor::new(b"RC\x80\x02\x00\x01\x00\x00".to_vec());
+    assert!(matches!(MessageCodec::with_max_frame_len(64).read_message(&mut src), Err(WireError::FrameTooLarge { .. })));
+}
+
+#[test]
+fn write_rejects_long_fields() {
+    let msg = AddRecord { recid: 1, atype: 0, rtype: Bytes::from("T".repeat(256)), rname: Bytes::from_static(b"NAME") };
+    let mut buf = Vec::new();
+    assert!(matches!(write_message(&mut buf, &Message::AddRecord(msg)), Err(WireError::FieldTooLong { field: "rtype", .. })));
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn announcement_reader() {
+    let reader = AnnouncementReader::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let dst = reader.socket().local_addr().unwrap();
+    let sender = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+
+    sender.send_to(b"bad", dst).unwrap();
+    let msg = Announcement::new(Ipv4Addr::BROADCAST, 40000, 7);
+    sender.send_to(&msg.encode(), dst).unwrap();
+
+    assert!(matches!(reader.recv(), Err(WireError::Truncated { .. })));
+    let (received, src) = reader.recv().unwrap();
+    assert_eq!(received, msg);
+    assert_eq!(src, sender.local_addr().unwrap());
+}
@@ -0,0 +1,257 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+use bytes::{BufMut, Bytes, BytesMut};
+use wire::{AddInfo, AddRecord, AddRecordType, ClientGreet, DelRecord, Message, MessageCodec, MessageHeader, Ping, Pong, ServerGreet, UnknownPolicy, Utf8Mode, UploadDone, WireError, DEFAULT_MAX_FRAME_LEN, HEADER_LEN};
+
+fn session() -> Vec<Message> {
+    vec![
+        Message::ServerGreet(ServerGreet::default()),
+        Message::ClientGreet(ClientGreet { serv_key: 0x12345678, ..Default::default() }),
+        Message::AddRecord(AddRecord::new(100, AddRecordType::Record, "ai", "DEV:RECASTER:RUST").unwrap()),
+        Message::AddRecord(AddRecord::new(100, AddRecordType::Alias, "ai", "DEV:ALIAS").unwrap()),
+        Message::AddInfo(AddInfo::new(100, "recordDesc", "Rust Recaster").unwrap()),
+        Message::DelRecord(DelRecord { recid: 100 }),
+        Message::UploadDone(UploadDone),
+        Message::Ping(Ping { nonce: 42 }),
+        Message::Pong(Pong { nonce: 42 }),
+    ]
+}
+
+fn encode_all(msgs: &[Message]) -> BytesMut {
+    let mut buf = BytesMut::new();
+    for msg in msgs {
+        msg.encode_to(&mut buf).unwrap();
+    }
+    buf
+}
+
+fn decode_all(buf: &mut BytesMut) -> Vec<Message> {
+    let mut msgs = Vec::new();
+    while let Some(msg) = MessageCodec::new().decode_message(buf).unwrap() {
+        msgs.push(msg);
+    }
+    msgs
+}
+
+#[test]
+fn round_trip() {
+    let msgs = session();
+    let mut buf = encode_all(&msgs);
+    assert_eq!(decode_all(&mut buf), msgs);
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn split_at_every_offset() {
+    let msgs = session();
+    let bytes = encode_all(&msgs);
+    for split in 0..=bytes.len() {
+        let mut buf = BytesMut::from(&bytes[..split]);
+        let mut decoded = decode_all(&mut buf);
+        buf.extend_from_slice(&bytes[split..]);
+        decoded.extend(decode_all(&mut buf));
+        assert_eq!(decoded, msgs, "split at offset {}", split);
+        assert!(buf.is_empty());
+    }
+}
+
+#[test]
+fn byte_at_a_time() {
+    let msgs = session();
+    let bytes = encode_all(&msgs);
+    let mut buf = BytesMut::new();
+    let mut decoded = Vec::new();
+    for byte in bytes.iter() {
+        buf.put_u8(*byte);
+        decoded.extend(decode_all(&mut buf));
+    }
+    assert_eq!(decoded, msgs);
+}
+
+#[test]
+fn bad_magic() {
+    let mut buf = BytesMut::from(&[0x41, 0x42, 0x80, 0x02, 0, 0, 0, 4, 0, 0, 0, 1][..]);
+    assert!(matches!(MessageCodec::new().decode_message(&mut buf), Err(WireError::BadMagic(0x4142))));
+}
+
+/// Unknown message 0x0042 with a 3 byte body followed by a Ping
+const UNKNOWN_THEN_PING: [u8; 23] = [0x52, 0x43, 0x00, 0x42, 0, 0, 0, 3, 1, 2, 3, 0x52, 0x43, 0x80, 0x02, 0, 0, 0, 4, 0, 0, 0, 7];
+
+#[test]
+fn unknown_message_is_preserved() {
+    let mut buf = BytesMut::from(&UNKNOWN_THEN_PING[..]);
+    assert_eq!(decode_all(&mut buf), vec![
+        Message::Unknown { msg_id: 0x0042, body: Bytes::from_static(&[1, 2, 3]) },
+        Message::Ping(Ping { nonce: 7 }),
+    ]);
+
+    let mut encoded = BytesMut::new();
+    Message::Unknown { msg_id: 0x0042, body: Bytes::from_static(&[1, 2, 3]) }.encode_to(&mut encoded).unwrap();
+    assert_eq!(&encoded[..], &UNKNOWN_THEN_PING[..11]);
+}
+
+#[test]
+fn unknown_message_is_skipped() {
+    let mut codec = MessageCodec::new();
+    codec.set_unknown_policy(UnknownPolicy::Skip);
+    let mut buf = BytesMut::from(&UNKNOWN_THEN_PING[..]);
+    assert_eq!(codec.decode_message(&mut buf).unwrap(), Some(Message::Ping(Ping { nonce: 7 })));
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn unknown_message_is_rejected() {
+    let mut codec = MessageCodec::new();
+    codec.set_unknown_policy(UnknownPolicy::Reject);
+    let mut buf = BytesMut::from(&UNKNOWN_THEN_PING[..]);
+    assert!(matches!(codec.decode_message(&mut buf), Err(WireError::UnknownMessageId(0x0042))));
+}
+
+#[test]
+fn length_longer_than_body() {
+    // Ping with one extra trailing byte
+    let mut buf = BytesMut::from(&[0x52, 0x43, 0x80, 0x02, 0, 0, 0, 5, 0, 0, 0, 1, 0][..]);
+    assert!(matches!(MessageCodec::new().decode_message(&mut buf), Err(WireError::BadLength { msg_id: 0x8002, len: 5 })));
+}
+
+#[test]
+fn length_shorter_than_body() {
+    // AddRecord announcing a 4 byte name in a body that only holds 2
+    let mut buf = BytesMut::from(&[0x52, 0x43, 0x00, 0x03, 0, 0, 0, 12, 0, 0, 0, 100, 0, 2, 0, 4, b'a', b'i', b'A', b'B'][..]);
+    assert!(matches!(MessageCodec::new().decode_message(&mut buf), Err(WireError::Truncated { needed: 4, available: 2 })));
+}
+
+#[test]
+fn constructors_reject_long_fields() {
+    let long_type = "a".repeat(256);
+    assert!(matches!(AddRecord::new(1, AddRecordType::Record, long_type, "NAME"), Err(WireError::FieldTooLong { field: "rtype", len: 256, max: 255 })));
+    let long_value = "v".repeat(65536);
+    assert!(matches!(AddInfo::new(1, "key", long_value), Err(WireError::FieldTooLong { field: "value", len: 65536, max: 65535 })));
+}
+
+#[test]
+fn encoder_rejects_long_fields() {
+    let msg = AddRecord { recid: 1, atype: 0, rtype: Bytes::from_static(b"ai"), rname: Bytes::from("N".repeat(65536)) };
+    let mut buf = BytesMut::new();
+    assert!(matches!(Message::AddRecord(msg).encode_to(&mut buf), Err(WireError::FieldTooLong { field: "rname", .. })));
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn decoded_strings_share_the_read_buffer() {
+    let mut buf = encode_all(&[Message::AddInfo(AddInfo::new(100, "recordDesc", "Rust Recaster").unwrap())]);
+    let range = buf.as_ptr() as usize..buf.as_ptr() as usize + buf.len();
+    match MessageCodec::new().decode_message(&mut buf).unwrap() {
+        Some(Message::AddInfo(msg)) => {
+            assert!(range.contains(&(msg.key.as_ptr() as usize)));
+            assert!(range.contains(&(msg.value.as_ptr() as usize)));
+        },
+        other => panic!("unexpected message {:?}", other),
+    }
+}
+
+#[test]
+fn default_max_frame_len_fits_largest_messages() {
+    let record = AddRecord::new(1, AddRecordType::Record, "t".repeat(255), "n".repeat(65535)).unwrap();
+    let info = AddInfo::new(1, "k".repeat(255), "v".repeat(65535)).unwrap();
+    let msgs = vec![Message::AddRecord(record), Message::AddInfo(info)];
+    let mut buf = encode_all(&msgs);
+    assert_eq!(buf.len(), 2 * DEFAULT_MAX_FRAME_LEN);
+    assert_eq!(decode_all(&mut buf), msgs);
+}
+
+#[test]
+fn oversize_frame_is_rejected_from_the_header() {
+    // Header announcing a 4 GiB body, nothing else buffered
+    let mut buf = BytesMut::from(&[0x52, 0x43, 0x00, 0x03, 0xff, 0xff, 0xff, 0xff][..]);
+    assert!(matches!(MessageCodec::new().decode_message(&mut buf), Err(WireError::FrameTooLarge { max: DEFAULT_MAX_FRAME_LEN, .. })));
+
+    let codec = MessageCodec::with_max_frame_len(12);
+    let mut buf = encode_all(&[Message::Ping(Ping { nonce: 1 }), Message::ClientGreet(ClientGreet { serv_key: 1, ..Default::default() })]);
+    assert_eq!(codec.decode_message(&mut buf).unwrap(), Some(Message::Ping(Ping { nonce: 1 })));
+    assert!(matches!(codec.decode_message(&mut buf), Err(WireError::FrameTooLarge { len: 16, max: 12 })));
+}
+
+/// AddRecord whose name "DEV:TEMP\xb0C" is Latin-1 rather than UTF-8
+const LATIN1_RECORD: [u8; 28] = [
+    0x52, 0x43, 0x00, 0x03, 0, 0, 0, 20,
+    0, 0, 0, 100, 0, 2, 0, 10,
+    b'a', b'i', b'D', b'E', b'V', b':', b'T', b'E', b'M', b'P', 0xb0, b'C',
+];
+
+fn decode_latin1(mode: Utf8Mode) -> Result<Option<Message>, WireError> {
+    let mut codec = MessageCodec::new();
+    codec.set_utf8_mode(mode);
+    codec.decode_message(&mut BytesMut::from(&LATIN1_RECORD[..]))
+}
+
+#[test]
+fn strict_utf8_rejects_invalid_names() {
+    assert!(matches!(decode_latin1(Utf8Mode::Strict), Err(WireError::InvalidUtf8(_))));
+}
+
+#[test]
+fn lossy_utf8_replaces_invalid_names() {
+    match decode_latin1(Utf8Mode::Lossy).unwrap() {
+        Some(Message::AddRecord(msg)) => assert_eq!(&msg.rname[..], "DEV:TEMP\u{fffd}C".as_bytes()),
+        other => panic!("unexpected message {:?}", other),
+    }
+}
+
+#[test]
+fn raw_utf8_keeps_exact_bytes() {
+    let msg = decode_latin1(Utf8Mode::Raw).unwrap().unwrap();
+    let mut encoded = BytesMut::new();
+    msg.encode_to(&mut encoded).unwrap();
+    assert_eq!(&encoded[..], &LATIN1_RECORD[..]);
+}
+
+#[test]
+fn encoded_len_matches_encoding() {
+    for msg in session() {
+        let mut buf = BytesMut::new();
+        msg.encode_to(&mut buf).unwrap();
+        assert_eq!(msg.encoded_len(), buf.len());
+    }
+}
+
+#[test]
+fn decode_frame_needs_exactly_one_frame() {
+    let codec = MessageCodec::new();
+    let buf = encode_all(&[Message::Ping(Ping { nonce: 9 })]).freeze();
+    assert_eq!(codec.frame_len(&buf[..7]).unwrap(), None);
+    assert_eq!(codec.frame_len(&buf).unwrap(), Some(12));
+    assert_eq!(codec.decode_frame(buf.clone()).unwrap(), Some(Message::Ping(Ping { nonce: 9 })));
+    assert!(matches!(codec.decode_frame(buf.slice(..10)), Err(WireError::Truncated { needed: 12, available: 10 })));
+}
+
+#[test]
+fn tokio_codec_matches_core() {
+    use tokio_util::codec::{Decoder, Encoder};
+
+    let mut buf = BytesMut::new();
+    for msg in session() {
+        MessageCodec::new().encode(msg, &mut buf).unwrap();
+    }
+    assert_eq!(buf, encode_all(&session()));
+
+    let mut msgs = Vec::new();
+    while let Some(msg) = MessageCodec::new().decode(&mut buf).unwrap() {
+        msgs.push(msg);
+    }
+    assert_eq!(msgs, session());
+}
+
+#[test]
+fn header_bytes() {
+    let header = MessageHeader::new(0x0003, 0x01020304);
+    let bytes = header.as_bytes();
+    assert_eq!(&bytes[..], b"RC\x00\x03\x01\x02\x03\x04");
+    assert_eq!(MessageHeader::peek(&bytes), Some(header));
+    assert_eq!(MessageHeader::peek(&bytes[..HEADER_LEN - 1]), None);
+}
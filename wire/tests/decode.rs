@@ -0,0 +1,93 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+use bytes::{BufMut, BytesMut};
+use tokio_util::codec::Decoder;
+use wire::{AddInfo, AddRecord, ClientGreet, DelRecord, Message, MessageCodec, Pong};
+
+/// Frame `body` with an "RC" header for `msg_id`
+fn frame(msg_id: u16, body: &[u8]) -> BytesMut {
+    let mut buf = BytesMut::new();
+    buf.put_u16(0x5243);
+    buf.put_u16(msg_id);
+    buf.put_u32(body.len() as u32);
+    buf.put_slice(body);
+    buf
+}
+
+fn decode(mut buf: BytesMut) -> Message {
+    let msg = MessageCodec::new().decode(&mut buf).unwrap().expect("whole frame buffered");
+    assert!(buf.is_empty(), "{} bytes left over", buf.len());
+    msg
+}
+
+#[test]
+fn client_greet() {
+    let msg = decode(frame(0x0001, &[0, 0, 0, 0, 0x12, 0x34, 0x56, 0x78]));
+    assert!(matches!(msg, Message::ClientGreet(ClientGreet { serv_key: 0x12345678, .. })), "{:?}", msg);
+}
+
+#[test]
+fn pong() {
+    let msg = decode(frame(0x0002, &[0, 0, 0, 42]));
+    assert!(matches!(msg, Message::Pong(Pong { nonce: 42 })), "{:?}", msg);
+}
+
+#[test]
+fn add_record() {
+    let mut body = vec![0, 0, 0, 100, 1, 2, 0, 8];
+    body.extend_from_slice(b"aiDEV:TEMP");
+    let msg = decode(frame(0x0003, &body));
+    assert!(matches!(&msg, Message::AddRecord(AddRecord { recid: 100, atype: 1, rtype, rname, .. }) if rtype == "ai" && rname == "DEV:TEMP"), "{:?}", msg);
+}
+
+#[test]
+fn del_record() {
+    let msg = decode(frame(0x0004, &[0, 0, 0, 100]));
+    assert!(matches!(msg, Message::DelRecord(DelRecord { recid: 100 })), "{:?}", msg);
+}
+
+#[test]
+fn upload_done() {
+    let msg = decode(frame(0x0005, &[0, 0, 0, 0]));
+    assert!(matches!(msg, Message::UploadDone(_)), "{:?}", msg);
+}
+
+#[test]
+fn add_info() {
+    let mut body = vec![0, 0, 0, 100, 10, 0, 0, 4];
+    body.extend_from_slice(b"recordDescTEMP");
+    let msg = decode(frame(0x0006, &body));
+    assert!(matches!(&msg, Message::AddInfo(AddInfo { recid: 100, key, value, .. }) if key == "recordDesc" && value == "TEMP"), "{:?}", msg);
+}
+
+#[test]
+fn consecutive_frames() {
+    let mut buf = frame(0x0004, &[0, 0, 0, 1]);
+    buf.extend_from_slice(&frame(0x0002, &[0, 0, 0, 2]));
+    assert!(matches!(MessageCodec::new().decode(&mut buf).unwrap(), Some(Message::DelRecord(DelRecord { recid: 1 }))));
+    assert!(matches!(MessageCodec::new().decode(&mut buf).unwrap(), Some(Message::Pong(Pong { nonce: 2 }))));
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn truncated_body_is_an_error() {
+    // Header length shorter than the fixed fields
+    assert!(MessageCodec::new().decode(&mut frame(0x0002, &[0, 0])).is_err());
+    assert!(MessageCodec::new().decode(&mut frame(0x0001, &[0, 0, 0, 0])).is_err());
+    // String lengths pointing past the end of the body, even when more bytes follow in the buffer
+    let mut buf = frame(0x0003, &[0, 0, 0, 100, 0, 2, 0, 200, b'a', b'i']);
+    buf.extend_from_slice(&frame(0x0004, &[0, 0, 0, 1]));
+    assert!(MessageCodec::new().decode(&mut buf).is_err());
+    assert!(MessageCodec::new().decode(&mut frame(0x0006, &[0, 0, 0, 100, 10, 0, 0, 0])).is_err());
+}
+
+#[test]
+fn invalid_utf8_is_an_error() {
+    let body = [0, 0, 0, 100, 0, 2, 0, 1, b'a', b'i', 0xff];
+    assert!(MessageCodec::new().decode(&mut frame(0x0003, &body)).is_err());
+}
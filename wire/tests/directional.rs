@@ -0,0 +1,125 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+use bytes::{Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+use wire::{AddInfo, AddRecord, AddRecordType, ClientCodec, ClientGreet, ClientMessage, Message, Ping, Pong, ServerCodec, ServerGreet, ServerMessage, UnknownPolicy, UploadDone, Utf8Mode, WireError};
+
+fn client_messages() -> Vec<ClientMessage> {
+    vec![
+        ClientMessage::ClientGreet(ClientGreet { serv_key: 7, ..Default::default() }),
+        ClientMessage::AddRecord(AddRecord::new(100, AddRecordType::Record, "ai", "DEV:TEMP").unwrap()),
+        ClientMessage::AddInfo(AddInfo::new(100, "EGU", "C").unwrap()),
+        ClientMessage::UploadDone(UploadDone),
+        ClientMessage::Pong(Pong { nonce: 3 }),
+    ]
+}
+
+#[test]
+fn caster_to_receiver() {
+    let mut buf = BytesMut::new();
+    let mut client = ClientCodec::new();
+    for msg in client_messages() {
+        client.encode(msg, &mut buf).unwrap();
+    }
+    let mut server = ServerCodec::new();
+    let mut decoded = Vec::new();
+    while let Some(msg) = server.decode(&mut buf).unwrap() {
+        decoded.push(msg);
+    }
+    assert_eq!(decoded, client_messages());
+}
+
+#[test]
+fn receiver_to_caster() {
+    let mut buf = BytesMut::new();
+    let mut server = ServerCodec::new();
+    server.encode(ServerMessage::ServerGreet(ServerGreet::default()), &mut buf).unwrap();
+    server.encode(ServerMessage::Ping(Ping { nonce: 3 }), &mut buf).unwrap();
+    let client = ClientCodec::new();
+    assert_eq!(client.decode_message(&mut buf).unwrap(), Some(ServerMessage::ServerGreet(ServerGreet::default())));
+    assert_eq!(client.decode_message(&mut buf).unwrap(), Some(ServerMessage::Ping(Ping { nonce: 3 })));
+    assert_eq!(client.decode_message(&mut buf).unwrap(), None);
+}
+
+#[test]
+fn wrong_direction_is_rejected() {
+    let mut buf = BytesMut::new();
+    ClientMessage::UploadDone(UploadDone).encode_to(&mut buf).unwrap();
+    assert!(matches!(ClientCodec::new().decode_message(&mut buf), Err(WireError::WrongDirection(0x0005))));
+
+    let mut buf = BytesMut::new();
+    ServerMessage::Ping(Ping { nonce: 1 }).encode_to(&mut buf).unwrap();
+    assert!(matches!(ServerCodec::new().decode_message(&mut buf), Err(WireError::WrongDirection(0x8002))));
+
+    assert!(matches!(ClientMessage::try_from(Message::ServerGreet(ServerGreet::default())), Err(WireError::WrongDirection(0x8001))));
+    assert!(matches!(ServerMessage::try_from(Message::Pong(Pong { nonce: 1 })), Err(WireError::WrongDirection(0x0002))));
+}
+
+#[test]
+fn unknown_messages_follow_the_policy() {
+    let mut buf = BytesMut::new();
+    Message::Unknown { msg_id: 0x8042, body: Bytes::from_static(&[1]) }.encode_to(&mut buf).unwrap();
+    Message::Ping(Ping { nonce: 1 }).encode_to(&mut buf).unwrap();
+
+    let client = ClientCodec::new();
+    assert_eq!(client.decode_message(&mut buf.clone()).unwrap(), Some(ServerMessage::Unknown { msg_id: 0x8042, body: Bytes::from_static(&[1]) }));
+
+    let mut client = ClientCodec::new();
+    client.set_unknown_policy(UnknownPolicy::Skip);
+    assert_eq!(client.decode_message(&mut buf).unwrap(), Some(ServerMessage::Ping(Ping { nonce: 1 })));
+}
+
+#[test]
+fn decoding_settings_are_forwarded() {
+    let mut buf = BytesMut::new();
+    let msg = ClientMessage::AddRecord(AddRecord { recid: 1, atype: 0, rtype: Bytes::from_static(b"ai"), rname: Bytes::from_static(b"DEV:\xff") });
+    msg.encode_to(&mut buf).unwrap();
+
+    assert!(matches!(ServerCodec::new().decode_message(&mut buf.clone()), Err(WireError::InvalidUtf8(_))));
+    let mut server = ServerCodec::new();
+    server.set_utf8_mode(Utf8Mode::Raw);
+    assert_eq!(server.decode_message(&mut buf.clone()).unwrap(), Some(msg));
+
+    let server = ServerCodec::with_max_frame_len(16);
+    assert_eq!(server.codec().max_frame_len(), 16);
+    assert!(matches!(server.decode_message(&mut buf), Err(WireError::FrameTooLarge { max: 16, .. })));
+    let client = ClientCodec::with_max_frame_len(8);
+    let mut ping = BytesMut::new();
+    Message::Ping(Ping { nonce: 1 }).encode_to(&mut ping).unwrap();
+    assert!(matches!(client.decode_message(&mut ping), Err(WireError::FrameTooLarge { len: 12, max: 8 })));
+}
+
+#[test]
+fn encoded_len_matches_message() {
+    for msg in client_messages() {
+        let mut buf = BytesMut::new();
+        msg.encode_to(&mut buf).unwrap();
+        assert_eq!(buf.len(), msg.encoded_len());
+        assert_eq!(Message::from(msg).encoded_len(), buf.len());
+    }
+}
+
+#[test]
+fn invalid_unknown_messages_are_not_encoded() {
+    // A known ID would be decoded as that message on the other end
+    let mut buf = BytesMut::new();
+    let err = ClientCodec::new().encode(ClientMessage::Unknown { msg_id: 0x0004, body: Bytes::from_static(&[0; 4]) }, &mut buf);
+    assert!(matches!(err, Err(WireError::KnownMessageId(0x0004))));
+    let err = ServerCodec::new().encode(ServerMessage::Unknown { msg_id: 0x8001, body: Bytes::new() }, &mut buf);
+    assert!(matches!(err, Err(WireError::KnownMessageId(0x8001))));
+
+    // and a body over the frame limit would be rejected by the decoder
+    let body = Bytes::from(vec![0; wire::DEFAULT_MAX_FRAME_LEN]);
+    let err = ClientCodec::new().encode(ClientMessage::Unknown { msg_id: 0x0042, body }, &mut buf);
+    assert!(matches!(err, Err(WireError::FrameTooLarge { max: wire::DEFAULT_MAX_FRAME_LEN, .. })));
+    assert!(buf.is_empty());
+
+    let body = Bytes::from(vec![0; wire::DEFAULT_MAX_FRAME_LEN - 8]);
+    ClientCodec::new().encode(ClientMessage::Unknown { msg_id: 0x0042, body }, &mut buf).unwrap();
+    assert_eq!(buf.len(), wire::DEFAULT_MAX_FRAME_LEN);
+}
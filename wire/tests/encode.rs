@@ -0,0 +1,40 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+use bytes::BytesMut;
+use tokio_util::codec::{Decoder, Encoder};
+use wire::{DelRecord, Message, MessageCodec, Ping, ServerGreet};
+
+fn encode(msg: Message) -> BytesMut {
+    let mut buf = BytesMut::new();
+    MessageCodec::new().encode(msg, &mut buf).unwrap();
+    buf
+}
+
+fn round_trip(msg: Message) {
+    let mut buf = encode(msg.clone());
+    assert_eq!(MessageCodec::new().decode(&mut buf).unwrap(), Some(msg));
+    assert!(buf.is_empty(), "{} bytes left over", buf.len());
+}
+
+#[test]
+fn server_greet() {
+    assert_eq!(&encode(Message::ServerGreet(ServerGreet::default()))[..], b"RC\x80\x01\x00\x00\x00\x01\x00");
+    round_trip(Message::ServerGreet(ServerGreet::default()));
+}
+
+#[test]
+fn ping() {
+    assert_eq!(&encode(Message::Ping(Ping { nonce: 0x01020304 }))[..], b"RC\x80\x02\x00\x00\x00\x04\x01\x02\x03\x04");
+    round_trip(Message::Ping(Ping { nonce: 0x01020304 }));
+}
+
+#[test]
+fn del_record() {
+    assert_eq!(&encode(Message::DelRecord(DelRecord { recid: 100 }))[..], b"RC\x00\x04\x00\x00\x00\x04\x00\x00\x00\x64");
+    round_trip(Message::DelRecord(DelRecord { recid: 100 }));
+}
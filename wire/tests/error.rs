@@ -0,0 +1,70 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+use std::{error::Error, io};
+
+use bytes::{BufMut, BytesMut};
+use tokio_util::codec::Decoder;
+use wire::{MessageCodec, UnknownPolicy, WireError};
+
+fn frame(magic: u16, msg_id: u16, body: &[u8]) -> BytesMut {
+    let mut buf = BytesMut::new();
+    buf.put_u16(magic);
+    buf.put_u16(msg_id);
+    buf.put_u32(body.len() as u32);
+    buf.put_slice(body);
+    buf
+}
+
+fn decode_err(mut buf: BytesMut) -> WireError {
+    let mut codec = MessageCodec::new();
+    codec.set_unknown_policy(UnknownPolicy::Reject);
+    match codec.decode(&mut buf) {
+        Err(err) => err,
+        Ok(msg) => panic!("expected an error, decoded {:?}", msg),
+    }
+}
+
+#[test]
+fn bad_magic() {
+    let err = decode_err(frame(0x5858, 0x0002, &[0, 0, 0, 1]));
+    assert!(matches!(err, WireError::BadMagic(0x5858)), "{}", err);
+    assert_eq!(err.to_string(), "bad magic id 0x5858, expected 0x5243");
+}
+
+#[test]
+fn unknown_message_id() {
+    let err = decode_err(frame(0x5243, 0x0042, &[]));
+    assert!(matches!(err, WireError::UnknownMessageId(0x0042)), "{}", err);
+}
+
+#[test]
+fn truncated() {
+    let err = decode_err(frame(0x5243, 0x0002, &[0, 0]));
+    assert!(matches!(err, WireError::Truncated { needed: 4, available: 2 }), "{}", err);
+}
+
+#[test]
+fn bad_length() {
+    let err = decode_err(frame(0x5243, 0x0004, &[0, 0, 0, 1, 0, 0]));
+    assert!(matches!(err, WireError::BadLength { msg_id: 0x0004, len: 6 }), "{}", err);
+}
+
+#[test]
+fn invalid_utf8() {
+    let err = decode_err(frame(0x5243, 0x0003, &[0, 0, 0, 100, 0, 2, 0, 1, b'a', b'i', 0xff]));
+    assert!(matches!(err, WireError::InvalidUtf8(_)), "{}", err);
+    assert!(err.source().is_some());
+}
+
+#[test]
+fn io() {
+    // Framed streams convert socket errors into the codec error
+    let err = WireError::from(io::Error::new(io::ErrorKind::ConnectionReset, "reset"));
+    assert!(matches!(&err, WireError::Io(source) if source.kind() == io::ErrorKind::ConnectionReset), "{}", err);
+    assert_eq!(err.source().unwrap().to_string(), "reset");
+}
@@ -0,0 +1,117 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+//! Golden frames built by the reference RecCeiver code rather than captured from a live session,
+//! see `fixtures/generate.py` for how they are generated and how to replace them with captured bytes
+
+use std::net::{Ipv4Addr, SocketAddr};
+
+use bytes::{Bytes, BytesMut};
+use wire::{AddInfo, AddRecord, AddRecordType, Announcement, ClientGreet, DelRecord, Message, MessageCodec, Ping, Pong, ServerGreet, UploadDone};
+
+fn fixtures() -> Vec<(&'static str, &'static [u8], Message)> {
+    vec![
+        ("server_greet", include_bytes!("fixtures/server_greet.bin"), Message::ServerGreet(ServerGreet::default())),
+        ("client_greet", include_bytes!("fixtures/client_greet.bin"), Message::ClientGreet(ClientGreet { serv_key: 0x12345678, ..Default::default() })),
+        ("ping", include_bytes!("fixtures/ping.bin"), Message::Ping(Ping { nonce: 0xcafef00d })),
+        ("pong", include_bytes!("fixtures/pong.bin"), Message::Pong(Pong { nonce: 0xcafef00d })),
+        ("add_record", include_bytes!("fixtures/add_record.bin"), Message::AddRecord(AddRecord::new(100, AddRecordType::Record, "ai", "DEV:TEMP").unwrap())),
+        ("add_record_alias", include_bytes!("fixtures/add_record_alias.bin"), Message::AddRecord(AddRecord::new(100, AddRecordType::Alias, "ai", "DEV:TEMP:ALIAS").unwrap())),
+        ("add_info", include_bytes!("fixtures/add_info.bin"), Message::AddInfo(AddInfo::new(100, "recordDesc", "Temperature").unwrap())),
+        ("add_info_empty", include_bytes!("fixtures/add_info_empty.bin"), Message::AddInfo(AddInfo::new(100, "EGU", Bytes::new()).unwrap())),
+        ("del_record", include_bytes!("fixtures/del_record.bin"), Message::DelRecord(DelRecord { recid: 100 })),
+        ("upload_done", include_bytes!("fixtures/upload_done.bin"), Message::UploadDone(UploadDone)),
+    ]
+}
+
+fn decode_one(frame: &[u8]) -> Message {
+    let mut buf = BytesMut::from(frame);
+    let msg = MessageCodec::new().decode_message(&mut buf).unwrap().unwrap();
+    assert!(buf.is_empty());
+    msg
+}
+
+#[test]
+fn decoder_matches_fixtures() {
+    for (name, frame, expected) in fixtures() {
+        assert_eq!(decode_one(frame), expected, "{}", name);
+    }
+}
+
+#[test]
+fn encoder_matches_fixtures() {
+    for (name, frame, msg) in fixtures() {
+        let mut buf = BytesMut::new();
+        msg.encode_to(&mut buf).unwrap();
+        assert_eq!(&buf[..], frame, "{}", name);
+        assert_eq!(msg.encoded_len(), frame.len(), "{}", name);
+    }
+}
+
+#[test]
+fn fixtures_decode_as_one_stream() {
+    let mut buf = BytesMut::new();
+    for (_, frame, _) in fixtures() {
+        buf.extend_from_slice(frame);
+    }
+    for (name, _, expected) in fixtures() {
+        assert_eq!(MessageCodec::new().decode_message(&mut buf).unwrap(), Some(expected), "{}", name);
+    }
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn client_greet_version_and_type() {
+    // Version and type bytes are decoded and sent back out, only the reserved bytes are zeroed
+    let mut frame = include_bytes!("fixtures/client_greet.bin").to_vec();
+    assert_eq!(&frame[8..12], &[0, 0, 0, 0]);
+    frame[8] = 1;
+    frame[9] = 2;
+    frame[10] = 0xff;
+    let msg = decode_one(&frame);
+    assert_eq!(msg, Message::ClientGreet(ClientGreet { version: 1, client_type: 2, serv_key: 0x12345678 }));
+
+    let mut buf = BytesMut::new();
+    msg.encode_to(&mut buf).unwrap();
+    frame[10] = 0;
+    assert_eq!(&buf[..], &frame[..]);
+}
+
+#[test]
+fn server_greet_type() {
+    let mut frame = include_bytes!("fixtures/server_greet.bin").to_vec();
+    frame[8] = 3;
+    let msg = decode_one(&frame);
+    assert_eq!(msg, Message::ServerGreet(ServerGreet { server_type: 3 }));
+
+    let mut buf = BytesMut::new();
+    msg.encode_to(&mut buf).unwrap();
+    assert_eq!(&buf[..], &frame[..]);
+}
+
+#[test]
+fn upload_done_trailing_word() {
+    // The body is one word whose value is not used
+    let mut frame = include_bytes!("fixtures/upload_done.bin").to_vec();
+    assert_eq!(&frame[4..], &[0, 0, 0, 4, 0, 0, 0, 0]);
+    frame[11] = 1;
+    assert_eq!(decode_one(&frame), Message::UploadDone(UploadDone));
+}
+
+#[test]
+fn announcement_fixtures() {
+    let datagram = include_bytes!("fixtures/announcement.bin");
+    let msg = Announcement::decode(datagram).unwrap();
+    assert_eq!(msg, Announcement::new(Ipv4Addr::new(10, 0, 0, 1), 5050, 0xdeadbeef));
+    assert_eq!(&msg.encode()[..], &datagram[..]);
+
+    let datagram = include_bytes!("fixtures/announcement_broadcast.bin");
+    let msg = Announcement::decode(datagram).unwrap();
+    assert_eq!(&msg.encode()[..], &datagram[..]);
+    let src: SocketAddr = "192.168.1.20:5049".parse().unwrap();
+    assert_eq!(msg.resolve(src), "192.168.1.20:5050".parse().unwrap());
+}
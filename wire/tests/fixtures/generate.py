@@ -0,0 +1,89 @@
+#!/usr/bin/env python3
+# This file is part of Recsync-rs.
+# Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+#
+# This project is licensed under both the MIT License and the BSD 3-Clause License.
+# You must comply with both licenses to use, modify, or distribute this software.
+# See the LICENSE file for details.
+
+"""Regenerate the golden fixtures used by wire/tests/fixtures.rs.
+
+The frames are built by the reference RecCeiver itself, recceiver/recast.py
+and recceiver/announce.py in ChannelFinder/recsync: the header is written by
+CastReceiver.writeMsg and every body is packed with the module's own struct,
+the same one the RecCeiver unpacks client messages with. Nothing is re-typed
+here, so a change to the reference layouts shows up in the fixtures.
+
+Needs a checkout of ChannelFinder/recsync and twisted installed. Run from
+this directory with
+
+    PYTHONPATH=/path/to/recsync/server python3 generate.py
+
+They are still not captured traffic. To replace a fixture with captured
+bytes, run the reference RecCeiver and a RecCaster IOC, capture with
+
+    tcpdump -i lo -w recsync.pcap udp port 5049 or tcp
+
+check the session with `cargo run -p dissector -- recsync.pcap`, then save
+the TCP payload of the matching frame (e.g. Wireshark "Export Packet Bytes")
+over the .bin file and drop its entry below.
+"""
+
+import socket
+
+from recceiver import announce, recast
+
+
+class _Transport:
+    """Collects what the RecCeiver writes instead of sending it"""
+
+    def __init__(self):
+        self.data = b''
+
+    def write(self, data):
+        self.data += data
+
+
+class _Receiver:
+    def __init__(self):
+        self.transport = _Transport()
+
+
+def frame(msgid, body):
+    receiver = _Receiver()
+    recast.CastReceiver.writeMsg(receiver, msgid, body)
+    return receiver.transport.data
+
+
+def record(rid, rtype, rectype, recname):
+    return frame(0x0003, recast._c_rec.pack(rid, rtype, len(rectype), len(recname)) + rectype + recname)
+
+
+def info(rid, key, value):
+    return frame(0x0006, recast._c_info.pack(rid, len(key), len(value)) + key + value)
+
+
+def announcement(addr, port, key):
+    return announce._Ann.pack(recast._M, 0, socket.inet_aton(addr), port, 0, key)
+
+
+FIXTURES = {
+    'server_greet.bin': frame(0x8001, recast._s_greet.pack(0)),
+    'client_greet.bin': frame(0x0001, recast._c_greet.pack(0, 0, 0x12345678)),
+    'ping.bin': frame(0x8002, recast._ping.pack(0xcafef00d)),
+    'pong.bin': frame(0x0002, recast._ping.pack(0xcafef00d)),
+    'add_record.bin': record(100, 0, b'ai', b'DEV:TEMP'),
+    'add_record_alias.bin': record(100, 1, b'ai', b'DEV:TEMP:ALIAS'),
+    'add_info.bin': info(100, b'recordDesc', b'Temperature'),
+    'add_info_empty.bin': info(100, b'EGU', b''),
+    'del_record.bin': frame(0x0004, recast._ping.pack(100)),
+    # The RecCaster ends its upload with a single zero word
+    'upload_done.bin': frame(0x0005, recast._ping.pack(0)),
+    'announcement.bin': announcement('10.0.0.1', 5050, 0xdeadbeef),
+    'announcement_broadcast.bin': announcement('255.255.255.255', 5050, 0xdeadbeef),
+}
+
+if __name__ == '__main__':
+    for name, data in FIXTURES.items():
+        with open(name, 'wb') as f:
+            f.write(data)
@@ -0,0 +1,67 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+use arbitrary::{Arbitrary, Unstructured};
+use bytes::BytesMut;
+use proptest::{collection::vec, prelude::*};
+use wire::{strategy, Announcement, Message, MessageCodec};
+
+fn encode(msg: &Message) -> BytesMut {
+    let mut buf = BytesMut::new();
+    msg.encode_to(&mut buf).unwrap();
+    buf
+}
+
+proptest! {
+    #[test]
+    fn message_round_trip(msg in strategy::message()) {
+        let mut buf = encode(&msg);
+        prop_assert_eq!(buf.len(), msg.encoded_len());
+        prop_assert_eq!(MessageCodec::new().decode_message(&mut buf).unwrap(), Some(msg));
+        prop_assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn stream_round_trip(msgs in vec(strategy::message(), 0..16), split in any::<prop::sample::Index>()) {
+        let mut encoded = BytesMut::new();
+        for msg in &msgs {
+            encoded.extend_from_slice(&encode(msg));
+        }
+        // Deliver the stream in two parts split at an arbitrary offset
+        let mut buf = encoded.split_to(split.index(encoded.len() + 1));
+        let codec = MessageCodec::new();
+        let mut decoded = Vec::new();
+        while let Some(msg) = codec.decode_message(&mut buf).unwrap() {
+            decoded.push(msg);
+        }
+        buf.extend_from_slice(&encoded);
+        while let Some(msg) = codec.decode_message(&mut buf).unwrap() {
+            decoded.push(msg);
+        }
+        prop_assert_eq!(decoded, msgs);
+        prop_assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn announcement_round_trip(msg in strategy::announcement()) {
+        let encoded = msg.encode();
+        prop_assert_eq!(encoded.len(), msg.encoded_len());
+        prop_assert_eq!(Announcement::decode(&encoded).unwrap(), msg);
+    }
+
+    #[test]
+    fn arbitrary_round_trip(data in vec(any::<u8>(), 0..512)) {
+        let mut u = Unstructured::new(&data);
+        if let Ok(msg) = Message::arbitrary(&mut u) {
+            let mut buf = encode(&msg);
+            prop_assert_eq!(MessageCodec::new().decode_message(&mut buf).unwrap(), Some(msg));
+        }
+        if let Ok(msg) = Announcement::arbitrary(&mut u) {
+            prop_assert_eq!(Announcement::decode(&msg.encode()).unwrap(), msg);
+        }
+    }
+}
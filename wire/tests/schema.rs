@@ -0,0 +1,85 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+use bytes::{Buf, BytesMut};
+use proptest::prelude::*;
+use wire::{schema::{self, FieldKind}, strategy, Message, MessageID, DEFAULT_MAX_FRAME_LEN, HEADER_LEN};
+
+const MSG_IDS: [u16; 8] = [0x8001, 0x0001, 0x8002, 0x0002, 0x0003, 0x0004, 0x0005, 0x0006];
+
+fn get_uint(buf: &mut &[u8], size: usize) -> usize {
+    buf.get_uint(size) as usize
+}
+
+#[test]
+fn every_message_has_a_layout() {
+    assert_eq!(schema::LAYOUTS.len(), MSG_IDS.len());
+    for msg_id in MSG_IDS {
+        assert!(MessageID::try_from(msg_id).is_ok());
+        let layout = schema::layout(msg_id).unwrap();
+        assert_eq!(layout.msg_id, msg_id);
+    }
+    assert!(schema::layout(0x0042).is_none());
+}
+
+#[test]
+fn length_prefixes_match_text_fields() {
+    for layout in schema::LAYOUTS {
+        let lengths: Vec<_> = layout.fields.iter().filter(|f| matches!(f.kind, FieldKind::Length(_))).map(|f| f.name).collect();
+        let texts: Vec<_> = layout.fields.iter().filter(|f| f.kind == FieldKind::Text).map(|f| f.name).collect();
+        assert_eq!(lengths, texts, "{}", layout.name);
+
+        // Text fields come after all the fixed size fields
+        let first_text = layout.fields.iter().position(|f| f.kind == FieldKind::Text).unwrap_or(layout.fields.len());
+        assert!(layout.fields[first_text..].iter().all(|f| f.kind == FieldKind::Text), "{}", layout.name);
+    }
+}
+
+#[test]
+fn fixed_lengths() {
+    let fixed_len = |msg_id| schema::layout(msg_id).unwrap().fixed_len();
+    assert_eq!(fixed_len(0x8001), 1);
+    assert_eq!(fixed_len(0x0001), 8);
+    assert_eq!(fixed_len(0x8002), 4);
+    assert_eq!(fixed_len(0x0002), 4);
+    assert_eq!(fixed_len(0x0003), 8);
+    assert_eq!(fixed_len(0x0004), 4);
+    assert_eq!(fixed_len(0x0005), 4);
+    assert_eq!(fixed_len(0x0006), 8);
+    assert_eq!(DEFAULT_MAX_FRAME_LEN, HEADER_LEN + 8 + 255 + 65535);
+}
+
+proptest! {
+    /// Walk encoded frames field by field with the layout alone
+    #[test]
+    fn encoded_frames_follow_layout(msg in strategy::message()) {
+        prop_assume!(!matches!(msg, Message::Unknown { .. }));
+        let mut buf = BytesMut::new();
+        msg.encode_to(&mut buf).unwrap();
+        let layout = schema::layout(msg.msg_id()).unwrap();
+
+        let mut frame = &buf[..];
+        frame.advance(4);
+        let body_len = frame.get_u32() as usize;
+        prop_assert_eq!(frame.len(), body_len);
+
+        let mut lengths = Vec::new();
+        for field in layout.fields {
+            match field.kind {
+                FieldKind::Value(size) => frame.advance(size),
+                FieldKind::Padding(size) => prop_assert_eq!(get_uint(&mut frame, size), 0, "{}.{}", layout.name, field.name),
+                FieldKind::Length(size) => lengths.push(get_uint(&mut frame, size)),
+                FieldKind::Text => {
+                    let len = lengths.remove(0);
+                    prop_assert!(frame.len() >= len, "{}.{}", layout.name, field.name);
+                    frame.advance(len);
+                },
+            }
+        }
+        prop_assert!(frame.is_empty());
+    }
+}
@@ -0,0 +1,59 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+#![cfg(feature = "serde")]
+
+use std::net::Ipv4Addr;
+
+use bytes::Bytes;
+use wire::{AddInfo, AddRecord, AddRecordType, Announcement, ClientGreet, DelRecord, Message, MessageHeader, Ping, Pong, ServerGreet, UploadDone};
+
+#[test]
+fn messages_round_trip_as_json_lines() {
+    let msgs = vec![
+        Message::ServerGreet(ServerGreet::default()),
+        Message::ClientGreet(ClientGreet { serv_key: 0x12345678, ..Default::default() }),
+        Message::AddRecord(AddRecord::new(100, AddRecordType::Record, "ai", "DEV:RECASTER:RUST").unwrap()),
+        Message::AddInfo(AddInfo::new(100, "recordDesc", "Rust Recaster").unwrap()),
+        Message::DelRecord(DelRecord { recid: 100 }),
+        Message::UploadDone(UploadDone),
+        Message::Ping(Ping { nonce: 42 }),
+        Message::Pong(Pong { nonce: 42 }),
+        Message::Unknown { msg_id: 0x0042, body: Bytes::from_static(&[0, 1, 0xff]) },
+    ];
+    let lines: Vec<String> = msgs.iter().map(|msg| serde_json::to_string(msg).unwrap()).collect();
+    assert_eq!(lines[2], r#"{"AddRecord":{"recid":100,"atype":0,"rtype":"ai","rname":"DEV:RECASTER:RUST"}}"#);
+    assert_eq!(lines[8], r#"{"Unknown":{"msg_id":66,"body":[0,1,255]}}"#);
+
+    let decoded: Vec<Message> = lines.iter().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(decoded, msgs);
+}
+
+#[test]
+fn messages_round_trip_as_bincode() {
+    let msgs = vec![
+        Message::AddRecord(AddRecord::new(100, AddRecordType::Record, "ai", "DEV:RECASTER:RUST").unwrap()),
+        Message::AddInfo(AddInfo { recid: 100, key: Bytes::from_static(b"recordDesc"), value: Bytes::from_static(b"\xff\xfe") }),
+        Message::Unknown { msg_id: 0x0042, body: Bytes::from_static(&[0, 1, 0xff]) },
+    ];
+    for msg in msgs {
+        let encoded = bincode::serialize(&msg).unwrap();
+        assert_eq!(bincode::deserialize::<Message>(&encoded).unwrap(), msg);
+    }
+}
+
+#[test]
+fn announcement_and_header_round_trip() {
+    let announcement = Announcement::new(Ipv4Addr::new(10, 0, 0, 1), 40000, 7);
+    let json = serde_json::to_string(&announcement).unwrap();
+    assert_eq!(json, r#"{"id":21059,"server_addr":"10.0.0.1","server_port":40000,"server_key":7}"#);
+    assert_eq!(serde_json::from_str::<Announcement>(&json).unwrap(), announcement);
+
+    let header = MessageHeader::new(0x8002, 4);
+    let json = serde_json::to_string(&header).unwrap();
+    assert_eq!(serde_json::from_str::<MessageHeader>(&json).unwrap(), header);
+}
@@ -0,0 +1,140 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+use wire::session::{Phase, Session, SessionChecker};
+use wire::{AddInfo, AddRecord, AddRecordType, ClientGreet, DelRecord, Direction, Ping, Pong, ServerGreet, Transcript, UploadDone, WireError};
+
+fn check(transcript: &str) -> Result<SessionChecker, (usize, WireError)> {
+    let transcript: Transcript = transcript.parse().unwrap();
+    let mut checker = SessionChecker::new();
+    for (i, (direction, msg)) in transcript.entries.iter().enumerate() {
+        checker.check(*direction, msg).map_err(|err| (i + 1, err))?;
+    }
+    Ok(checker)
+}
+
+#[test]
+fn legal_session() {
+    let checker = check("
+        S->C Ping nonce=1
+        C->S Pong nonce=1
+        S->C ServerGreet
+        C->S ClientGreet key=7
+        C->S AddRecord recid=100 type=ai name=A
+        S->C Ping nonce=2
+        C->S AddRecord recid=100 kind=alias type=ai name=B
+        C->S AddInfo recid=100 key=EGU value=C
+        C->S AddRecord recid=101 type=ao name=D
+        C->S DelRecord recid=101
+        C->S Pong nonce=2
+        S->C Unknown msg_id=0x8042 body=00
+        C->S UploadDone
+        S->C Ping nonce=3
+        C->S Pong nonce=3
+    ").unwrap();
+    assert_eq!(checker.phase(), Phase::Established);
+    assert_eq!(checker.records(), 1);
+    assert!(checker.has_record(100));
+}
+
+#[test]
+fn out_of_order_messages() {
+    let greeted = "S->C ServerGreet\nC->S ClientGreet key=7\n";
+    let cases = [
+        ("C->S ClientGreet key=7", 1, Phase::AwaitServerGreet),
+        ("S->C ServerGreet\nC->S AddRecord recid=1 type=ai name=A", 2, Phase::AwaitClientGreet),
+        ("S->C ServerGreet\nS->C ServerGreet", 2, Phase::AwaitClientGreet),
+        ("C->S ServerGreet", 1, Phase::AwaitServerGreet),
+        ("S->C Pong nonce=1", 1, Phase::AwaitServerGreet),
+    ];
+    for (transcript, line, phase) in cases {
+        match check(transcript) {
+            Err((got_line, WireError::UnexpectedMessage { phase: got_phase, .. })) => assert_eq!((got_line, got_phase), (line, phase), "{}", transcript),
+            other => panic!("{}: {:?}", transcript, other.map(|checker| checker.phase())),
+        }
+    }
+
+    let after_upload = format!("{}C->S UploadDone\nC->S AddRecord recid=1 type=ai name=A", greeted);
+    assert!(matches!(check(&after_upload), Err((4, WireError::UnexpectedMessage { phase: Phase::Established, direction: Direction::ClientToServer, msg_id: 0x0003 }))));
+}
+
+#[test]
+fn unknown_recids() {
+    let greeted = "S->C ServerGreet\nC->S ClientGreet key=7\n";
+    for (body, recid) in [
+        ("C->S AddInfo recid=100 key=EGU value=C", 100),
+        ("C->S AddRecord recid=100 kind=alias type=ai name=B", 100),
+        ("C->S AddRecord recid=100 type=ai name=A\nC->S DelRecord recid=100\nC->S DelRecord recid=100", 100),
+        ("C->S AddRecord recid=100 type=ai name=A\nC->S AddInfo recid=101 key=EGU value=C", 101),
+    ] {
+        let transcript = format!("{}{}", greeted, body);
+        assert!(matches!(check(&transcript), Err((_, WireError::UnknownRecid(got))) if got == recid), "{}", transcript);
+    }
+}
+
+#[test]
+fn rejected_message_leaves_session_unchanged() {
+    let mut checker = check("S->C ServerGreet\nC->S ClientGreet key=7").unwrap();
+    let info = wire::Message::AddInfo(AddInfo::new(1, "EGU", "C").unwrap());
+    assert!(checker.check(Direction::ClientToServer, &info).is_err());
+    assert_eq!(checker.phase(), Phase::Uploading);
+}
+
+#[test]
+fn typed_session() {
+    let (session, greet) = Session::new().server_greet(ServerGreet::default());
+    assert_eq!(greet, wire::Message::ServerGreet(ServerGreet::default()));
+    let (mut session, _) = session.client_greet(ClientGreet { serv_key: 7, ..Default::default() });
+    assert!(matches!(session.add_info(AddInfo::new(100, "EGU", "C").unwrap()), Err(WireError::UnknownRecid(100))));
+    session.add_record(AddRecord::new(100, AddRecordType::Record, "ai", "A").unwrap()).unwrap();
+    session.add_info(AddInfo::new(100, "EGU", "C").unwrap()).unwrap();
+    session.del_record(DelRecord { recid: 100 }).unwrap();
+    let (session, done) = session.upload_done(UploadDone);
+    assert_eq!(done, wire::Message::UploadDone(UploadDone));
+    assert_eq!(session.checker().phase(), Phase::Established);
+}
+
+#[test]
+fn typed_session_keepalive() {
+    let mut session = Session::new();
+    assert_eq!(session.ping(Ping { nonce: 1 }), wire::Message::Ping(Ping { nonce: 1 }));
+    assert_eq!(session.pong(Pong { nonce: 1 }), wire::Message::Pong(Pong { nonce: 1 }));
+    let (session, _) = session.server_greet(ServerGreet::default());
+    let (mut session, _) = session.client_greet(ClientGreet { serv_key: 7, ..Default::default() });
+    session.ping(Ping { nonce: 2 });
+    session.add_record(AddRecord::new(100, AddRecordType::Record, "ai", "A").unwrap()).unwrap();
+    session.pong(Pong { nonce: 2 });
+    let (mut session, _) = session.upload_done(UploadDone);
+    session.ping(Ping { nonce: 3 });
+    session.pong(Pong { nonce: 3 });
+    assert_eq!(session.checker().phase(), Phase::Established);
+    assert_eq!(session.checker().records(), 1);
+}
+
+#[test]
+fn records_deleted_after_the_upload() {
+    let mut checker = check("
+        S->C ServerGreet
+        C->S ClientGreet key=7
+        C->S AddRecord recid=100 type=ai name=A
+        C->S AddRecord recid=101 type=ai name=B
+        C->S UploadDone
+        C->S DelRecord recid=100
+    ").unwrap();
+    assert_eq!(checker.phase(), Phase::Established);
+    assert!(!checker.has_record(100) && checker.has_record(101));
+    let del = wire::Message::DelRecord(DelRecord { recid: 100 });
+    assert!(matches!(checker.check(Direction::ClientToServer, &del), Err(WireError::UnknownRecid(100))));
+
+    let (session, _) = Session::new().server_greet(ServerGreet::default());
+    let (mut session, _) = session.client_greet(ClientGreet { serv_key: 7, ..Default::default() });
+    session.add_record(AddRecord::new(100, AddRecordType::Record, "ai", "A").unwrap()).unwrap();
+    let (mut session, _) = session.upload_done(UploadDone);
+    session.del_record(DelRecord { recid: 100 }).unwrap();
+    assert!(matches!(session.del_record(DelRecord { recid: 100 }), Err(WireError::UnknownRecid(100))));
+    assert_eq!(session.checker().records(), 0);
+}
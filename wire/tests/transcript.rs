@@ -0,0 +1,101 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+use bytes::Bytes;
+use proptest::prelude::*;
+use wire::{strategy, AddInfo, AddRecord, AddRecordType, ClientGreet, Direction, Message, Ping, ServerGreet, Transcript, UploadDone, WireError};
+
+const SESSION: &str = r#"
+# Upload of one record
+S->C ServerGreet
+C->S ClientGreet key=0x12345678
+C->S AddRecord recid=100 type=ai name=FOO
+C->S AddRecord recid=100 kind=alias type=ai name=FOO:ALIAS   # trailing comment
+C->S AddInfo recid=100 key=recordDesc value="Temperature \"inside\"\x01\xff"
+C->S UploadDone
+
+S->C Ping nonce=7
+"#;
+
+#[test]
+fn parse_session() {
+    let transcript: Transcript = SESSION.parse().unwrap();
+    assert_eq!(transcript.entries, vec![
+        (Direction::ServerToClient, Message::ServerGreet(ServerGreet::default())),
+        (Direction::ClientToServer, Message::ClientGreet(ClientGreet { serv_key: 0x12345678, ..Default::default() })),
+        (Direction::ClientToServer, Message::AddRecord(AddRecord::new(100, AddRecordType::Record, "ai", "FOO").unwrap())),
+        (Direction::ClientToServer, Message::AddRecord(AddRecord::new(100, AddRecordType::Alias, "ai", "FOO:ALIAS").unwrap())),
+        (Direction::ClientToServer, Message::AddInfo(AddInfo::new(100, "recordDesc", Bytes::from_static(b"Temperature \"inside\"\x01\xff")).unwrap())),
+        (Direction::ClientToServer, Message::UploadDone(UploadDone)),
+        (Direction::ServerToClient, Message::Ping(Ping { nonce: 7 })),
+    ]);
+    assert_eq!(transcript.messages(Direction::ServerToClient).count(), 2);
+}
+
+#[test]
+fn print_session() {
+    let transcript: Transcript = SESSION.parse().unwrap();
+    assert_eq!(transcript.to_string(), concat!(
+        "S->C ServerGreet\n",
+        "C->S ClientGreet key=0x12345678\n",
+        "C->S AddRecord recid=100 type=ai name=FOO\n",
+        "C->S AddRecord recid=100 kind=alias type=ai name=FOO:ALIAS\n",
+        "C->S AddInfo recid=100 key=recordDesc value=\"Temperature \\\"inside\\\"\\x01\\xff\"\n",
+        "C->S UploadDone\n",
+        "S->C Ping nonce=7\n",
+    ));
+    assert_eq!(transcript.to_string().parse::<Transcript>().unwrap(), transcript);
+}
+
+#[test]
+fn single_messages() {
+    assert_eq!("Unknown msg_id=0x0042 body=0102ff".parse::<Message>().unwrap(), Message::Unknown { msg_id: 0x42, body: Bytes::from_static(&[1, 2, 0xff]) });
+    assert_eq!(Message::Unknown { msg_id: 0x42, body: Bytes::new() }.to_string(), "Unknown msg_id=0x0042 body=\"\"");
+    assert_eq!(Message::AddInfo(AddInfo::new(1, "EGU", "").unwrap()).to_string(), "AddInfo recid=1 key=EGU value=\"\"");
+    assert_eq!("ServerGreet type=3".parse::<Message>().unwrap(), Message::ServerGreet(ServerGreet { server_type: 3 }));
+    let greet = Message::ClientGreet(ClientGreet { version: 1, client_type: 2, serv_key: 7 });
+    assert_eq!(greet.to_string(), "ClientGreet key=0x00000007 version=1 type=2");
+    assert_eq!(greet.to_string().parse::<Message>().unwrap(), greet);
+    assert_eq!("AddRecord name=X recid=0x10 type=ai kind=7".parse::<Message>().unwrap(), Message::AddRecord(AddRecord { recid: 16, atype: 7, rtype: Bytes::from_static(b"ai"), rname: Bytes::from_static(b"X") }));
+}
+
+#[test]
+fn errors_report_the_line() {
+    let cases = [
+        ("S->C ServerGreet\nC->S Ping", 2, "missing nonce"),
+        ("X->Y ServerGreet", 1, "expected C->S or S->C"),
+        ("C->S Ping nonce=1 extra=2", 1, "unexpected field extra"),
+        ("C->S Ping nonce=1 nonce=2", 1, "duplicate field nonce"),
+        ("C->S Ping nonce=-1", 1, "bad number for nonce: -1"),
+        ("C->S Hello", 1, "unknown message Hello"),
+        ("S->C Unknown msg_id=0x8002 body=00000001", 1, "unknown message uses the known message id 0x8002"),
+        ("\n\nC->S AddInfo recid=1 key=\"abc value=1", 3, "unterminated string in key"),
+    ];
+    for (text, line, reason) in cases {
+        match text.parse::<Transcript>() {
+            Err(WireError::Syntax { line: got_line, reason: got_reason }) => assert_eq!((got_line, got_reason.as_str()), (line, reason), "{}", text),
+            other => panic!("{}: {:?}", text, other),
+        }
+    }
+    let long = format!("AddRecord recid=1 type={} name=X", "T".repeat(256));
+    assert!(matches!(long.parse::<Message>(), Err(WireError::Syntax { .. })));
+}
+
+proptest! {
+    #[test]
+    fn text_round_trip(msg in strategy::message()) {
+        let text = msg.to_string();
+        prop_assert!(!text.contains('\n'));
+        prop_assert_eq!(text.parse::<Message>().unwrap(), msg);
+    }
+
+    #[test]
+    fn raw_bytes_round_trip(rtype in prop::collection::vec(any::<u8>(), 0..=255), rname in prop::collection::vec(any::<u8>(), 0..64)) {
+        let msg = Message::AddRecord(AddRecord { recid: 1, atype: 0, rtype: Bytes::from(rtype), rname: Bytes::from(rname) });
+        prop_assert_eq!(msg.to_string().parse::<Message>().unwrap(), msg);
+    }
+}
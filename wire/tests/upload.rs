@@ -0,0 +1,87 @@
+// This file is part of Recsync-rs.
+// Copyright (c) 2024 UK Research and Innovation, Science and Technology Facilities Council
+//
+// This project is licensed under both the MIT License and the BSD 3-Clause License.
+// You must comply with both licenses to use, modify, or distribute this software.
+// See the LICENSE file for details.
+
+use bytes::{Bytes, BytesMut};
+use wire::{AddRecordType, ClientGreet, ClientMessage, DelRecord, Message, MessageCodec, Pong, Upload, UploadDone, WireError};
+
+fn upload(records: u32) -> Upload {
+    let mut upload = Upload::new();
+    for recid in 100..100 + records {
+        upload.add_record(recid, AddRecordType::Record, "ai", format!("DEV:REC{}", recid)).unwrap();
+        upload.add_record(recid, AddRecordType::Alias, "ai", format!("DEV:ALIAS{}", recid)).unwrap();
+        upload.add_info(recid, "recordDesc", "Temperature").unwrap();
+        upload.add_info(recid, "EGU", Bytes::new()).unwrap();
+    }
+    upload.push(ClientMessage::DelRecord(DelRecord { recid: 100 })).unwrap();
+    upload
+}
+
+fn encode_one_by_one(upload: &Upload) -> BytesMut {
+    let mut buf = BytesMut::new();
+    for msg in upload.messages().iter().chain([&Message::UploadDone(UploadDone)]) {
+        msg.encode_to(&mut buf).unwrap();
+    }
+    buf
+}
+
+#[test]
+fn contiguous_encoding() {
+    let upload = upload(50);
+    let encoded = upload.encode();
+    assert_eq!(encoded.len(), upload.encoded_len());
+    assert_eq!(encoded, encode_one_by_one(&upload));
+
+    let mut buf = BytesMut::from(&encoded[..]);
+    let mut msgs = Vec::new();
+    while let Some(msg) = MessageCodec::new().decode_message(&mut buf).unwrap() {
+        msgs.push(msg);
+    }
+    assert_eq!(msgs.pop(), Some(Message::UploadDone(UploadDone)));
+    assert_eq!(msgs, upload.messages());
+}
+
+#[test]
+fn vectored_encoding() {
+    let upload = upload(50);
+    let mut scratch = BytesMut::new();
+    let slices = upload.io_slices(&mut scratch);
+    let joined: Vec<u8> = slices.iter().flat_map(|slice| slice.iter().copied()).collect();
+    assert_eq!(joined, upload.encode());
+    assert!(slices.iter().all(|slice| !slice.is_empty()));
+}
+
+#[test]
+fn empty_upload_is_upload_done() {
+    let upload = Upload::new();
+    assert_eq!(upload.encoded_len(), 12);
+    assert_eq!(&upload.encode()[..], b"RC\x00\x05\x00\x00\x00\x04\x00\x00\x00\x00");
+    assert_eq!(upload.io_slices(&mut BytesMut::new()).len(), 1);
+}
+
+#[test]
+fn rejects_long_fields() {
+    let mut upload = Upload::new();
+    assert!(matches!(upload.add_info(100, "K".repeat(256), "value"), Err(WireError::FieldTooLong { field: "key", .. })));
+    assert!(upload.messages().is_empty());
+    assert_eq!(upload.encoded_len(), 12);
+}
+
+#[test]
+fn rejects_messages_outside_the_upload() {
+    let mut upload = Upload::new();
+    let msgs = [
+        ClientMessage::ClientGreet(ClientGreet { serv_key: 1, ..Default::default() }),
+        ClientMessage::Pong(Pong { nonce: 1 }),
+        ClientMessage::UploadDone(UploadDone),
+        ClientMessage::Unknown { msg_id: 0x0042, body: Bytes::new() },
+    ];
+    for msg in msgs {
+        assert!(matches!(upload.push(msg), Err(WireError::UnexpectedMessage { .. })));
+    }
+    assert!(upload.messages().is_empty());
+    assert_eq!(upload.encoded_len(), 12);
+}